@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use snipren::{Error, OnConflict, Renamer, Result, SameData, SymlinkPolicy};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filters::{self, Filters};
+
+/// Environment variable that overrides the config file location.
+const CONFIG_ENV: &str = "SNIPREN_CONFIG";
+
+/// User defaults for `rn`, read from `$XDG_CONFIG_HOME/snipren/config.toml`.
+///
+/// Every field is optional; command line flags always take precedence.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Offer an interactive picker when several candidates match on a TTY
+    pub pick: bool,
+    /// Store the previous name in an extended attribute on renamed files
+    pub xattr: bool,
+    /// What to do when the target already exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<OnConflict>,
+    /// Move overwritten targets to the trash instead of deleting them
+    pub trash: bool,
+    /// Compare checksums when a move has to copy across filesystems
+    pub verify: bool,
+    /// Fsync the affected directories after every rename
+    pub sync: bool,
+    /// Consider directories as well as files
+    pub dirs: bool,
+    /// How symlinks among the candidates are treated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlinks: Option<SymlinkPolicy>,
+    /// Consider FIFOs, sockets and devices as well
+    pub special: bool,
+    /// Rewrite symlinks that point at a renamed file
+    pub update_symlinks: bool,
+    /// Where to look for those symlinks, instead of the file's directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink_tree: Option<PathBuf>,
+    /// Which name to rename when all candidates hold the same data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_data: Option<SameData>,
+    /// Treat byte-identical files as the same data, not only hardlinks
+    pub compare_contents: bool,
+    /// Only consider names matching one of these globs
+    pub include: Vec<String>,
+    /// Never consider names matching any of these globs
+    pub exclude: Vec<String>,
+    /// Consider hidden files even for a name that is not hidden
+    pub hidden: bool,
+    /// Leave out what `.gitignore`, `.ignore` and git's excludes ignore
+    /// (default on)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_files: Option<bool>,
+    /// Only consider entries modified less than this long ago, e.g. `2d`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
+    /// Only consider entries modified more than this long ago
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+    /// Only consider files at least this large, e.g. `10K`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+    /// Only consider files at most this large
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults when it does not exist.
+    pub fn load() -> Result<Config> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| Error::Config(format!("Invalid config '{}': {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(Error::io(
+                format!("Failed to read config '{}'", path.display()),
+                e,
+            )),
+        }
+    }
+}
+
+/// Starting point written by `rn config init`: every setting, commented out.
+const TEMPLATE: &str = "\
+# Configuration for rn, see `rn config` for the settings in effect.
+# Command line flags always take precedence.
+
+# Offer the interactive picker on ambiguous matches
+# pick = false
+
+# Store the previous name in a user.snipren.* extended attribute
+# xattr = false
+
+# Default strategy when the target exists:
+# refuse, overwrite, backup, numbered, suffix or skip
+# on_conflict = \"refuse\"
+
+# Move targets replaced by --force to the trash
+# trash = false
+
+# Checksum cross-filesystem copies before removing the source
+# verify = false
+
+# Fsync directories after every rename
+# sync = false
+
+# Consider directories as well as files
+# dirs = false
+
+# What to do with symlinks: skip them, rename the link, or rename the
+# file it points to (skip, link or target)
+# symlinks = \"link\"
+
+# Consider FIFOs, sockets and devices as well
+# special = false
+
+# Rewrite symlinks that point at a renamed file
+# update_symlinks = false
+
+# Look for those symlinks anywhere below this directory, instead of only
+# next to the renamed file
+# symlink_tree = \"/home/me/projects\"
+
+# Which name to rename when all candidates hold the same data:
+# refuse, best, oldest or newest
+# same_data = \"refuse\"
+
+# Treat byte-identical files as the same data, not only hardlinks
+# compare_contents = false
+
+# Only consider names matching one of these globs, and never those
+# matching any of the excludes
+# include = [\"*.csv\"]
+# exclude = [\"*~\", \"*.swp\", \"target/**\"]
+
+# Consider hidden files even when the new name is not hidden
+# hidden = false
+
+# Leave out what .gitignore, .ignore and git's global excludes ignore
+# ignore_files = true
+
+# Only consider entries modified within, or before, an age (s, m, h, d, w)
+# newer_than = \"30d\"
+# older_than = \"1h\"
+
+# Only consider files within a size range (K, M, G, T)
+# min_size = \"1K\"
+# max_size = \"100M\"
+";
+
+impl Config {
+    /// The settings as TOML, with defaults spelled out.
+    pub fn to_toml(&self) -> Result<String> {
+        let effective = Config {
+            on_conflict: Some(self.on_conflict.unwrap_or_default()),
+            symlinks: Some(self.symlinks.unwrap_or_default()),
+            same_data: Some(self.same_data.unwrap_or_default()),
+            ignore_files: Some(self.ignore_files.unwrap_or(true)),
+            ..self.clone()
+        };
+        toml::to_string(&effective).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// A renamer that picks and filters candidates, settles on one of several
+    /// holding the same data and updates symlinks the way the config says.
+    pub fn renamer(&self) -> Result<Renamer> {
+        Ok(self.renamer_with(self.filters()?))
+    }
+
+    /// Like [`Config::renamer`], with `filters` in place of the configured ones.
+    pub fn renamer_with(&self, filters: Filters) -> Renamer {
+        let renamer = Renamer::new()
+            .dirs(self.dirs)
+            .symlinks(self.symlinks.unwrap_or_default())
+            .special(self.special)
+            .update_symlinks(self.update_symlinks)
+            .symlink_tree(self.symlink_tree.clone())
+            .same_data(self.same_data.unwrap_or_default())
+            .compare_contents(self.compare_contents)
+            .hidden(self.hidden)
+            .ignore_files(self.ignore_files.unwrap_or(true));
+        filters.apply(renamer)
+    }
+
+    /// The candidate filters the config sets.
+    pub fn filters(&self) -> Result<Filters> {
+        let globs = |setting, patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| filters::config_value(setting, p, filters::parse_glob))
+                .collect::<Result<Vec<_>>>()
+        };
+        let age = |setting, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| filters::config_value(setting, v, filters::parse_duration))
+                .transpose()
+        };
+        let size = |setting, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| filters::config_value(setting, v, filters::parse_size))
+                .transpose()
+        };
+
+        Ok(Filters {
+            include: globs("include", &self.include)?,
+            exclude: globs("exclude", &self.exclude)?,
+            newer_than: age("newer_than", &self.newer_than)?,
+            older_than: age("older_than", &self.older_than)?,
+            min_size: size("min_size", &self.min_size)?,
+            max_size: size("max_size", &self.max_size)?,
+        })
+    }
+}
+
+/// Write the commented template to `path`, refusing to replace a config.
+pub fn init(path: &Path) -> Result<()> {
+    let fail = |e: io::Error| Error::io(format!("Failed to write config '{}'", path.display()), e);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(fail)?;
+    }
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(mut file) => io::Write::write_all(&mut file, TEMPLATE.as_bytes()).map_err(fail),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            Err(Error::TargetExists(path.to_path_buf()))
+        }
+        Err(e) => Err(fail(e)),
+    }
+}
+
+/// Resolve the config file path following the XDG base directory spec.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("snipren").join("config.toml"))
+}
+
+/// Resolve an XDG base directory, using `$HOME/<fallback>` when unset.
+pub fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(var) {
+        // The spec says relative paths must be ignored
+        Some(dir) if PathBuf::from(&dir).is_absolute() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)),
+    }
+}
@@ -0,0 +1,237 @@
+use snipren::{Error, Glob, Renamer, Result};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Narrowing of the candidates by name, age and size, from the config and
+/// the command line.
+#[derive(Debug, Default)]
+pub struct Filters {
+    /// Only names matching one of these are candidates
+    pub include: Vec<Glob>,
+    /// Names matching any of these are not
+    pub exclude: Vec<Glob>,
+    /// Only entries modified less than this long ago
+    pub newer_than: Option<Duration>,
+    /// Only entries modified more than this long ago
+    pub older_than: Option<Duration>,
+    /// Only files at least this many bytes long
+    pub min_size: Option<u64>,
+    /// Only files at most this many bytes long
+    pub max_size: Option<u64>,
+}
+
+impl Filters {
+    /// Add a filter to `renamer` for every constraint that is set.
+    pub fn apply(self, mut renamer: Renamer) -> Renamer {
+        let Filters {
+            include,
+            exclude,
+            newer_than,
+            older_than,
+            min_size,
+            max_size,
+        } = self;
+
+        renamer = renamer.include(include).exclude(exclude);
+        if newer_than.is_some() || older_than.is_some() {
+            let now = SystemTime::now();
+            renamer = renamer.filter(move |path| {
+                // A file from the future counts as just modified
+                let Some(age) = metadata(path)
+                    .and_then(|m| m.modified().ok())
+                    .map(|mtime| now.duration_since(mtime).unwrap_or_default())
+                else {
+                    return false;
+                };
+                newer_than.is_none_or(|limit| age < limit)
+                    && older_than.is_none_or(|limit| age > limit)
+            });
+        }
+        if min_size.is_some() || max_size.is_some() {
+            renamer = renamer.filter(move |path| match metadata(path) {
+                // Sizes only mean something for files
+                Some(m) if m.is_file() => {
+                    min_size.is_none_or(|min| m.len() >= min)
+                        && max_size.is_none_or(|max| m.len() <= max)
+                }
+                Some(_) => true,
+                None => false,
+            });
+        }
+        renamer
+    }
+}
+
+/// Metadata of what `path` leads to, or of the link itself when it dangles.
+fn metadata(path: &Path) -> Option<fs::Metadata> {
+    fs::metadata(path)
+        .or_else(|_| fs::symlink_metadata(path))
+        .ok()
+}
+
+/// Parse a glob, for clap and the config.
+pub fn parse_glob(value: &str) -> std::result::Result<Glob, String> {
+    Glob::new(value).map_err(|e| e.to_string())
+}
+
+/// Parse an age like `90s`, `30m`, `12h`, `2d` or `1w`.
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("'{}' is not an age like 30m, 12h, 2d or 1w", value);
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Parse a size in bytes, optionally with a `K`, `M`, `G` or `T` suffix
+/// (powers of 1024).
+pub fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let invalid = || format!("'{}' is not a size like 512, 10K, 5M or 1G", value);
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let scale: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(scale).ok_or_else(invalid)
+}
+
+/// Turn a parse failure of a config setting into a config error.
+pub fn config_value<T>(
+    setting: &str,
+    value: &str,
+    parse: fn(&str) -> std::result::Result<T, String>,
+) -> Result<T> {
+    parse(value).map_err(|e| Error::Config(format!("Invalid '{}' in config: {}", setting, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filters, parse_duration, parse_size};
+    use crate::testing::scratch;
+    use snipren::Renamer;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(
+            parse_duration("2d"),
+            Ok(Duration::from_secs(2 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            parse_duration("1w"),
+            Ok(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+        for invalid in [
+            "",
+            "30",
+            "m",
+            "2.5h",
+            "3y",
+            "-1d",
+            "30 m",
+            "99999999999999999999w",
+        ] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 << 10));
+        assert_eq!(parse_size("5m"), Ok(5 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert_eq!(parse_size("2T"), Ok(2 << 40));
+        for invalid in ["", "K", "1.5M", "10KB", "-1", "99999999T"] {
+            assert!(parse_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_filters_age_and_size() {
+        let dir = scratch("age");
+        fs::write(dir.join("notes.txt"), "x".repeat(2048)).unwrap();
+        fs::write(dir.join("notes.md"), "x").unwrap();
+        let old = fs::File::options()
+            .write(true)
+            .open(dir.join("notes.md"))
+            .unwrap();
+        old.set_modified(SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60))
+            .unwrap();
+        let source = |filters: Filters| {
+            filters
+                .apply(Renamer::new())
+                .infer(dir.join("notes.rst").to_str().unwrap())
+                .map(|rename| rename.unwrap().source)
+        };
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let newer = Filters {
+            newer_than: Some(day),
+            ..Filters::default()
+        };
+        assert_eq!(source(newer).unwrap(), dir.join("notes.txt"));
+        let older = Filters {
+            older_than: Some(day),
+            ..Filters::default()
+        };
+        assert_eq!(source(older).unwrap(), dir.join("notes.md"));
+
+        let large = Filters {
+            min_size: Some(1024),
+            ..Filters::default()
+        };
+        assert_eq!(source(large).unwrap(), dir.join("notes.txt"));
+        let small = Filters {
+            max_size: Some(1024),
+            ..Filters::default()
+        };
+        assert_eq!(source(small).unwrap(), dir.join("notes.md"));
+
+        // Sizes leave directories alone
+        fs::create_dir(dir.join("notes")).unwrap();
+        let renamer = Filters {
+            min_size: Some(1 << 20),
+            ..Filters::default()
+        }
+        .apply(Renamer::new().dirs(true));
+        let rename = renamer.infer(dir.join("notes_v2").to_str().unwrap());
+        assert_eq!(rename.unwrap().unwrap().source, dir.join("notes"));
+
+        // Both bounds at once, with nothing in between
+        let none = Filters {
+            newer_than: Some(day),
+            max_size: Some(1024),
+            ..Filters::default()
+        };
+        assert!(source(none).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,657 @@
+use serde::{Deserialize, Serialize};
+use snipren::{Error, MatchRule, Relinked, Renamed, Result, move_path, trash, untrash};
+use std::fs;
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::xdg_dir;
+
+/// Oldest entries are dropped once the journal grows past this many renames.
+const MAX_ENTRIES: usize = 1000;
+
+/// One successful rename, as recorded in the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the Unix epoch when the rename happened
+    pub timestamp: u64,
+    /// Working directory `rn` was invoked from
+    pub cwd: PathBuf,
+    /// Absolute path of the file before the rename
+    pub source: PathBuf,
+    /// Absolute path of the file after the rename
+    pub target: PathBuf,
+    /// Inode of the renamed file, used to verify it before undoing
+    pub inode: u64,
+    /// Rule that matched the source to the new name
+    pub rule: MatchRule,
+    /// Where a displaced target was backed up to, restored on undo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    /// Where an overwritten target went in the trash, restored on undo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trashed: Option<PathBuf>,
+    /// Symlinks rewritten to follow the rename, put back on undo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relinked: Vec<Relinked>,
+    /// Set while the rename is undone
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undone: bool,
+    /// Position on the redo stack; cleared once a new rename is recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redo_seq: Option<u64>,
+}
+
+impl Entry {
+    /// Build an entry for a rename that just completed.
+    pub fn new(renamed: &Renamed) -> Result<Entry> {
+        let Renamed {
+            source,
+            target,
+            conflict,
+            ..
+        } = renamed;
+        let metadata = fs::symlink_metadata(target)
+            .map_err(|e| Error::io(format!("Failed to stat '{}'", target.display()), e))?;
+        let cwd =
+            std::env::current_dir().map_err(|e| Error::io("Failed to get current directory", e))?;
+
+        Ok(Entry {
+            timestamp: now(),
+            cwd,
+            source: source.clone(),
+            target: target.clone(),
+            inode: metadata.ino(),
+            rule: renamed.rule,
+            backup: conflict.as_ref().and_then(|c| c.backup.clone()),
+            trashed: conflict.as_ref().and_then(|c| c.trashed.clone()),
+            relinked: renamed.relinked.clone(),
+            undone: false,
+            redo_seq: None,
+        })
+    }
+}
+
+/// The rename journal stored under `$XDG_STATE_HOME/snipren/journal.jsonl`.
+///
+/// An open journal holds an exclusive lock until it is dropped, so runs of
+/// `rn` side by side record their renames one after the other instead of
+/// overwriting each other's.
+pub struct Journal {
+    path: PathBuf,
+    entries: Vec<Entry>,
+    /// The lock file, locked while this is alive
+    _lock: fs::File,
+}
+
+impl Journal {
+    /// Load the journal, starting empty when none has been written yet.
+    pub fn open() -> Result<Journal> {
+        let path = xdg_dir("XDG_STATE_HOME", ".local/state")
+            .map(|dir| dir.join("snipren").join("journal.jsonl"))
+            .ok_or_else(|| {
+                Error::Config(
+                    "Cannot locate the journal: neither XDG_STATE_HOME nor HOME is set".to_string(),
+                )
+            })?;
+        Journal::at(path)
+    }
+
+    /// Lock and load the journal stored at `path`.
+    fn at(path: PathBuf) -> Result<Journal> {
+        let lock = lock(&path)?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(Error::io(
+                    format!("Failed to read journal '{}'", path.display()),
+                    e,
+                ));
+            }
+        };
+
+        let entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<Vec<Entry>, _>>()
+            .map_err(|e| {
+                Error::io(
+                    format!("Corrupt journal '{}'", path.display()),
+                    io::Error::new(io::ErrorKind::InvalidData, e),
+                )
+            })?;
+
+        Ok(Journal {
+            path,
+            entries,
+            _lock: lock,
+        })
+    }
+
+    /// Record a new rename. Like any editor, this discards the redo history.
+    pub fn record(&mut self, entry: Entry) -> Result<()> {
+        for e in &mut self.entries {
+            e.redo_seq = None;
+        }
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+        self.save()
+    }
+
+    /// Renames that can still be undone, most recent first.
+    pub fn active(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().rev().filter(|e| !e.undone)
+    }
+
+    /// Revert the `n`-th most recent rename (1 is the latest).
+    pub fn undo(&mut self, n: usize) -> Result<Entry> {
+        let index = self
+            .entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, e)| !e.undone)
+            .nth(n.saturating_sub(1))
+            .map(|(i, _)| i)
+            .ok_or_else(|| {
+                Error::Stale(match n {
+                    1 => "Nothing to undo.".to_string(),
+                    _ => format!("Nothing to undo: fewer than {} renames recorded.", n),
+                })
+            })?;
+
+        let entry = &self.entries[index];
+        move_verified(&entry.target, &entry.source, entry.inode)?;
+
+        // Bring a backed up target back to its name
+        if let Some(backup) = &entry.backup
+            && let Err(e) = move_free(backup, &entry.target)
+        {
+            eprintln!("Warning: backup not restored: {}", e);
+        }
+        // And an overwritten one out of the trash
+        if let Some(trashed) = &entry.trashed
+            && let Err(e) = restore_trashed(trashed, &entry.target)
+        {
+            eprintln!(
+                "Warning: overwritten file not restored from the trash: {}",
+                e
+            );
+        }
+        for relinked in entry.relinked.iter().rev() {
+            if let Err(e) = relinked.revert() {
+                eprintln!("Warning: symlink not restored: {}", e);
+            }
+        }
+
+        let seq = self.entries.iter().filter_map(|e| e.redo_seq).max();
+        let entry = &mut self.entries[index];
+        entry.inode = inode(&entry.source).unwrap_or(entry.inode);
+        entry.undone = true;
+        entry.redo_seq = Some(seq.map_or(1, |s| s + 1));
+        let entry = entry.clone();
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// Re-apply the most recently undone rename.
+    pub fn redo(&mut self) -> Result<Entry> {
+        let index = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.undone)
+            .filter_map(|(i, e)| e.redo_seq.map(|seq| (seq, i)))
+            .max()
+            .map(|(_, i)| i)
+            .ok_or_else(|| Error::Stale("Nothing to redo.".to_string()))?;
+
+        let entry = &self.entries[index];
+
+        // Displace the restored target to its backup again
+        if let Some(backup) = &entry.backup
+            && fs::symlink_metadata(&entry.target).is_ok()
+        {
+            move_free(&entry.target, backup)?;
+        }
+        // Or to the trash, wherever it lands this time
+        let retrashed = match &entry.trashed {
+            Some(_) if fs::symlink_metadata(&entry.target).is_ok() => Some(trash(&entry.target)?),
+            _ => None,
+        };
+        if let Err(e) = move_verified(&entry.source, &entry.target, entry.inode) {
+            if let Some(trashed) = &retrashed {
+                let _ = untrash(trashed, &entry.target);
+            }
+            return Err(e);
+        }
+        for relinked in &entry.relinked {
+            if let Err(e) = relinked.apply() {
+                eprintln!("Warning: symlink not updated: {}", e);
+            }
+        }
+
+        let entry = &mut self.entries[index];
+        entry.inode = inode(&entry.target).unwrap_or(entry.inode);
+        entry.undone = false;
+        entry.redo_seq = None;
+        if retrashed.is_some() {
+            entry.trashed = retrashed;
+        }
+        let entry = entry.clone();
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// Rewrite the journal atomically via a temporary file.
+    fn save(&self) -> Result<()> {
+        let fail = |e: io::Error| {
+            Error::io(
+                format!("Failed to write journal '{}'", self.path.display()),
+                e,
+            )
+        };
+
+        let tmp = self
+            .path
+            .with_extension(format!("jsonl.{}.tmp", std::process::id()));
+        let mut file = fs::File::create(&tmp).map_err(fail)?;
+        for entry in &self.entries {
+            let line = serde_json::to_string(entry)
+                .map_err(io::Error::from)
+                .map_err(fail)?;
+            writeln!(file, "{}", line).map_err(fail)?;
+        }
+        file.sync_all().map_err(fail)?;
+        fs::rename(&tmp, &self.path).map_err(fail)
+    }
+}
+
+/// Take an exclusive lock on the journal at `path`, waiting for any other
+/// process holding it. The lock is on a file of its own, as the journal
+/// itself is replaced on every save.
+fn lock(path: &Path) -> Result<fs::File> {
+    let lock = path.with_extension("lock");
+    let fail = |e: io::Error| Error::io(format!("Failed to lock journal '{}'", lock.display()), e);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(fail)?;
+    }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock)
+        .map_err(fail)?;
+    loop {
+        // SAFETY: flock only takes the descriptor of the open file
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(file);
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(fail(e));
+        }
+    }
+}
+
+/// Move `from` back to `to`, but only if `from` is still the recorded file
+/// and nothing has taken the name `to` in the meantime.
+fn move_verified(from: &Path, to: &Path, inode: u64) -> Result<()> {
+    match fs::symlink_metadata(from) {
+        Ok(metadata) if metadata.ino() == inode => {}
+        Ok(_) => {
+            return Err(Error::Stale(format!(
+                "'{}' is no longer the file that was renamed.",
+                from.display()
+            )));
+        }
+        Err(e) => {
+            return Err(Error::Stale(format!(
+                "'{}' is no longer there: {}",
+                from.display(),
+                e
+            )));
+        }
+    }
+
+    move_free(from, to)
+}
+
+/// Take `trashed` out of the trash back to `original`, refusing if
+/// `original` is taken.
+fn restore_trashed(trashed: &Path, original: &Path) -> Result<()> {
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(Error::TargetExists(original.to_path_buf()));
+    }
+    untrash(trashed, original)
+}
+
+/// Move `from` to `to`, refusing if `to` is taken. Like the rename itself,
+/// this copies across filesystems when it has to.
+fn move_free(from: &Path, to: &Path) -> Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(Error::TargetExists(to.to_path_buf()));
+    }
+
+    move_path(from, to, false)
+}
+
+/// The inode of `path`, which a move across filesystems changes.
+fn inode(path: &Path) -> Option<u64> {
+    fs::symlink_metadata(path).ok().map(|m| m.ino())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format seconds since the epoch as an ISO 8601 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, Journal, MAX_ENTRIES, format_timestamp};
+    use crate::testing::scratch;
+    use snipren::{Error, MatchRule, OnConflict, Renamed, Resolution, move_path};
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    /// Rename `from` to `to` in `dir` and record it, like `rn` does.
+    fn rename(journal: &mut Journal, dir: &Path, from: &str, to: &str) {
+        let (source, target) = (dir.join(from), dir.join(to));
+        fs::rename(&source, &target).unwrap();
+        let renamed = Renamed {
+            source,
+            target,
+            rule: MatchRule::Expansion,
+            conflict: None,
+            relinked: Vec::new(),
+        };
+        journal.record(Entry::new(&renamed).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_journal_undo_and_redo_in_order() {
+        let dir = scratch("order");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let path = dir.join("journal.jsonl");
+        let mut journal = Journal::at(path.clone()).unwrap();
+        rename(&mut journal, &dir, "a.txt", "a_v2.txt");
+        rename(&mut journal, &dir, "b.txt", "b_v2.txt");
+
+        assert_eq!(journal.undo(1).unwrap().source, dir.join("b.txt"));
+        assert_eq!(journal.undo(1).unwrap().source, dir.join("a.txt"));
+        assert!(dir.join("a.txt").exists() && dir.join("b.txt").exists());
+        assert!(matches!(journal.undo(1), Err(Error::Stale(_))));
+
+        // The state survives reopening, and redo goes back the way undo came
+        drop(journal);
+        let mut journal = Journal::at(path).unwrap();
+        assert_eq!(journal.redo().unwrap().target, dir.join("a_v2.txt"));
+        assert_eq!(journal.redo().unwrap().target, dir.join("b_v2.txt"));
+        assert!(matches!(journal.redo(), Err(Error::Stale(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_record_clears_redo_history() {
+        let dir = scratch("clear");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        rename(&mut journal, &dir, "a.txt", "a_v2.txt");
+        journal.undo(1).unwrap();
+
+        rename(&mut journal, &dir, "b.txt", "b_v2.txt");
+        assert!(matches!(journal.redo(), Err(Error::Stale(_))));
+        assert_eq!(journal.active().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_undo_nth_latest() {
+        let dir = scratch("nth");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        rename(&mut journal, &dir, "a.txt", "a_v2.txt");
+        rename(&mut journal, &dir, "b.txt", "b_v2.txt");
+        rename(&mut journal, &dir, "c.txt", "c_v2.txt");
+
+        assert_eq!(journal.undo(2).unwrap().source, dir.join("b.txt"));
+        assert!(dir.join("b.txt").exists() && dir.join("c_v2.txt").exists());
+        // Undone renames no longer count
+        assert_eq!(journal.undo(2).unwrap().source, dir.join("a.txt"));
+        assert!(matches!(journal.undo(2), Err(Error::Stale(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_undo_refuses_changed_files() {
+        let dir = scratch("refuse");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        rename(&mut journal, &dir, "a.txt", "a_v2.txt");
+        rename(&mut journal, &dir, "b.txt", "b_v2.txt");
+
+        // Another file in place of the renamed one
+        fs::write(dir.join("other"), "other").unwrap();
+        fs::rename(dir.join("other"), dir.join("b_v2.txt")).unwrap();
+        assert!(matches!(journal.undo(1), Err(Error::Stale(_))));
+
+        // The old name taken again
+        fs::write(dir.join("a.txt"), "new").unwrap();
+        assert!(matches!(journal.undo(2), Err(Error::TargetExists(_))));
+        assert_eq!(fs::read_to_string(dir.join("a_v2.txt")).unwrap(), "a");
+        assert_eq!(journal.active().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_restores_backup() {
+        let dir = scratch("backup");
+        fs::write(dir.join("a.txt"), "source").unwrap();
+        fs::write(dir.join("a_v2.txt"), "target").unwrap();
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+
+        fs::rename(dir.join("a_v2.txt"), dir.join("a_v2.txt~")).unwrap();
+        fs::rename(dir.join("a.txt"), dir.join("a_v2.txt")).unwrap();
+        let renamed = Renamed {
+            source: dir.join("a.txt"),
+            target: dir.join("a_v2.txt"),
+            rule: MatchRule::Expansion,
+            conflict: Some(Resolution {
+                strategy: OnConflict::Backup,
+                backup: Some(dir.join("a_v2.txt~")),
+                trashed: None,
+            }),
+            relinked: Vec::new(),
+        };
+        let entry = Entry::new(&renamed).unwrap();
+        journal.record(entry).unwrap();
+
+        journal.undo(1).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "source");
+        assert_eq!(fs::read_to_string(dir.join("a_v2.txt")).unwrap(), "target");
+        assert!(!dir.join("a_v2.txt~").exists());
+
+        journal.redo().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a_v2.txt")).unwrap(), "source");
+        assert_eq!(fs::read_to_string(dir.join("a_v2.txt~")).unwrap(), "target");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_restores_trashed_target() {
+        let dir = scratch("trashed");
+        fs::create_dir_all(dir.join("Trash/files")).unwrap();
+        fs::create_dir_all(dir.join("Trash/info")).unwrap();
+        fs::write(dir.join("a.txt"), "source").unwrap();
+        fs::write(dir.join("Trash/files/a_v2.txt"), "target").unwrap();
+        fs::write(dir.join("Trash/info/a_v2.txt.trashinfo"), "").unwrap();
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+
+        fs::rename(dir.join("a.txt"), dir.join("a_v2.txt")).unwrap();
+        let renamed = Renamed {
+            source: dir.join("a.txt"),
+            target: dir.join("a_v2.txt"),
+            rule: MatchRule::Expansion,
+            conflict: Some(Resolution {
+                strategy: OnConflict::Overwrite,
+                backup: None,
+                trashed: Some(dir.join("Trash/files/a_v2.txt")),
+            }),
+            relinked: Vec::new(),
+        };
+        journal.record(Entry::new(&renamed).unwrap()).unwrap();
+
+        let entry = journal.undo(1).unwrap();
+        assert_eq!(entry.trashed, Some(dir.join("Trash/files/a_v2.txt")));
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "source");
+        assert_eq!(fs::read_to_string(dir.join("a_v2.txt")).unwrap(), "target");
+        assert!(!dir.join("Trash/files/a_v2.txt").exists());
+        assert!(!dir.join("Trash/info/a_v2.txt.trashinfo").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_keeps_latest_entries() {
+        let dir = scratch("trim");
+        fs::write(dir.join("f0"), "").unwrap();
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        let renamed = Renamed {
+            source: dir.join("f"),
+            target: dir.join("f0"),
+            rule: MatchRule::Expansion,
+            conflict: None,
+            relinked: Vec::new(),
+        };
+        let entry = Entry::new(&renamed).unwrap();
+        for i in 0..MAX_ENTRIES + 5 {
+            journal.entries.push(Entry {
+                timestamp: i as u64,
+                ..entry.clone()
+            });
+        }
+        journal.record(entry).unwrap();
+        drop(journal);
+
+        let journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        assert_eq!(journal.entries.len(), MAX_ENTRIES);
+        assert_eq!(journal.entries[0].timestamp, 6);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_undo_across_filesystems() {
+        let dir = scratch("across");
+        // Only where a second filesystem is at hand
+        let other = Path::new("/dev/shm").join(format!("snipren-journal-{}", std::process::id()));
+        let device = |path: &Path| fs::metadata(path).map(|m| m.dev());
+        if fs::create_dir_all(&other).is_err() || device(&other).ok() == device(&dir).ok() {
+            let _ = fs::remove_dir_all(&other);
+            return;
+        }
+        let (source, target) = (dir.join("a.txt"), other.join("a_v2.txt"));
+        fs::write(&source, "a").unwrap();
+        move_path(&source, &target, false).unwrap();
+        let renamed = Renamed {
+            source: source.clone(),
+            target: target.clone(),
+            rule: MatchRule::Expansion,
+            conflict: None,
+            relinked: Vec::new(),
+        };
+        let mut journal = Journal::at(dir.join("journal.jsonl")).unwrap();
+        journal.record(Entry::new(&renamed).unwrap()).unwrap();
+
+        journal.undo(1).unwrap();
+        assert_eq!(fs::read_to_string(&source).unwrap(), "a");
+        assert!(fs::symlink_metadata(&target).is_err());
+        // The copy has a new inode, which redo checks against
+        journal.redo().unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "a");
+        assert!(fs::symlink_metadata(&source).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other).unwrap();
+    }
+
+    #[test]
+    fn test_journal_concurrent_records() {
+        let dir = scratch("concurrent");
+        let path = dir.join("journal.jsonl");
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let (dir, path) = (dir.clone(), path.clone());
+                std::thread::spawn(move || {
+                    for i in 0..10 {
+                        let name = format!("{}-{}", t, i);
+                        fs::write(dir.join(&name), "").unwrap();
+                        let mut journal = Journal::at(path.clone()).unwrap();
+                        rename(&mut journal, &dir, &name, &format!("{}_v2", name));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Every rename is kept, and no temporary file is left behind
+        assert_eq!(Journal::at(path).unwrap().entries.len(), 80);
+        assert!(
+            fs::read_dir(&dir).unwrap().all(|e| !e
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".tmp"))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_timestamp(4_107_542_399), "2100-02-28T23:59:59Z");
+    }
+}
@@ -0,0 +1,908 @@
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use snipren::{Error, OnConflict, Relinked, Rename, RenamePlan, Renamed, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+mod config;
+mod filters;
+mod interrupt;
+mod journal;
+mod output;
+mod prompt;
+mod provenance;
+mod select;
+#[cfg(test)]
+#[path = "../../testing.rs"]
+mod testing;
+
+use config::Config;
+use journal::{Entry, Journal};
+use output::{Format, Report};
+use select::Selection;
+
+#[derive(Parser, Debug)]
+#[command(name = "rn")]
+#[command(about = "A fast, safe, intent-aware rename utility", long_about = None)]
+#[command(after_help = EXIT_CODES_HELP)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The new filename(s) to rename to; several are renamed all or nothing.
+    /// `-` reads names from stdin. Put `--` before a name that is also a
+    /// subcommand, as in `rn -- undo`
+    #[arg(required_unless_present = "from_file")]
+    new_names: Vec<String>,
+
+    /// Read new filenames from FILE (`-` for stdin), one per line
+    #[arg(long, value_name = "FILE")]
+    from_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    select: Selection,
+
+    /// Rewrite symlinks that point at the renamed file, keeping them
+    /// relative or absolute; undo puts them back
+    #[arg(long, overrides_with = "no_update_symlinks")]
+    update_symlinks: bool,
+
+    /// Leave symlinks to the renamed file alone, even if enabled in the config
+    #[arg(long, overrides_with = "update_symlinks")]
+    no_update_symlinks: bool,
+
+    /// Look for symlinks to update anywhere below DIR, instead of only next
+    /// to the renamed file
+    #[arg(long, value_name = "DIR")]
+    symlink_tree: Option<PathBuf>,
+
+    /// Force rename even if target exists (same as --on-conflict=overwrite)
+    #[arg(short, long, conflicts_with = "on_conflict")]
+    force: bool,
+
+    /// What to do when the target already exists
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    on_conflict: Option<OnConflict>,
+
+    /// Pick interactively when several files match (requires a terminal)
+    #[arg(short, long, overrides_with = "no_pick")]
+    pick: bool,
+
+    /// Never prompt for a pick, even if enabled in the config
+    #[arg(long, overrides_with = "pick")]
+    no_pick: bool,
+
+    /// Ask for confirmation before renaming or overwriting
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Store the previous name in an extended attribute on the renamed file
+    #[arg(long, overrides_with = "no_xattr")]
+    xattr: bool,
+
+    /// Do not store the previous name, even if enabled in the config
+    #[arg(long, overrides_with = "xattr")]
+    no_xattr: bool,
+
+    /// Move an overwritten target to the trash instead of deleting it
+    #[arg(long, overrides_with = "no_trash")]
+    trash: bool,
+
+    /// Delete overwritten targets, even if trashing is enabled in the config
+    #[arg(long, overrides_with = "trash")]
+    no_trash: bool,
+
+    /// Compare checksums, not just sizes, when a move has to copy across filesystems
+    #[arg(long)]
+    verify: bool,
+
+    /// Fsync the affected directories so the rename survives a crash
+    #[arg(long, overrides_with = "no_sync")]
+    sync: bool,
+
+    /// Do not fsync directories, even if enabled in the config
+    #[arg(long, overrides_with = "sync")]
+    no_sync: bool,
+
+    /// Output format: human readable text or one JSON object per name
+    #[arg(long, value_enum, default_value_t, value_name = "FORMAT")]
+    format: Format,
+
+    /// Print only the renamed paths, NUL-terminated, for `xargs -0`; name
+    /// lists from `-`, --from-file or --candidates-from are read NUL-delimited too
+    #[arg(short = '0', long, conflicts_with = "format")]
+    print0: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Revert a previous rename recorded in the journal
+    Undo {
+        /// Which rename to revert, counting back from the most recent
+        #[arg(default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        n: u64,
+
+        /// List the renames that can be undone instead
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Re-apply the most recently undone rename
+    Redo,
+    /// Return a file to the previous name stored in its extended attributes
+    Restore {
+        /// The renamed file
+        file: PathBuf,
+    },
+    /// Print the file a new name would be renamed from, without renaming it
+    Which {
+        /// The new filename to resolve
+        new_name: String,
+
+        #[command(flatten)]
+        select: Selection,
+    },
+    /// Show every candidate for a new name and what a rename would do
+    Explain {
+        /// The new filename to explain
+        new_name: String,
+
+        #[command(flatten)]
+        select: Selection,
+    },
+    /// Show or create the configuration file
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+    /// Work out renames without performing them, for review and `rn apply`
+    Plan {
+        /// The new filenames to rename to
+        #[arg(required = true)]
+        new_names: Vec<String>,
+
+        /// Write the plan to FILE instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// What to do when a target already exists
+        #[arg(long, value_enum, value_name = "STRATEGY")]
+        on_conflict: Option<OnConflict>,
+
+        #[command(flatten)]
+        select: Selection,
+    },
+    /// Perform the renames in a plan, if nothing changed since it was made
+    Apply {
+        /// Plan written by `rn plan`
+        plan: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the settings in effect (the default)
+    Show,
+    /// Print where the config file is read from
+    Path,
+    /// Write a commented config file, unless one exists
+    Init,
+}
+
+/// Exit codes, one per outcome, so scripts can tell failures apart.
+/// Usage errors exit with 2, as reported by clap.
+mod exit {
+    pub const IO: i32 = 1;
+    pub const USAGE: i32 = 2;
+    pub const NO_MATCH: i32 = 3;
+    pub const AMBIGUOUS: i32 = 4;
+    pub const TARGET_EXISTS: i32 = 5;
+    pub const NOT_PERMITTED: i32 = 6;
+    pub const ABORTED: i32 = 7;
+    pub const STALE: i32 = 8;
+}
+
+const EXIT_CODES_HELP: &str = "\
+Exit status:
+  0  renamed (or skipped by --on-conflict=skip)
+  1  I/O error
+  2  usage, invalid name or invalid config
+  3  no matching file
+  4  ambiguous match
+  5  target already exists
+  6  not permitted (pre-flight check failed)
+  7  aborted at a prompt
+  8  journal, plan or recorded name no longer matches the filesystem";
+
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Io { .. } => exit::IO,
+        Error::InvalidName(_) | Error::Config(_) => exit::USAGE,
+        Error::NoMatch(_) => exit::NO_MATCH,
+        Error::Ambiguous { .. } => exit::AMBIGUOUS,
+        Error::TargetExists(_) => exit::TARGET_EXISTS,
+        Error::NotPermitted(_) => exit::NOT_PERMITTED,
+        Error::Aborted => exit::ABORTED,
+        Error::Stale(_) => exit::STALE,
+    }
+}
+
+/// Parse the command line, refusing a new name that is also a subcommand
+/// unless `--` marks it as a name: after a flag, clap would otherwise take
+/// `rn --sync undo` as a rename to `undo`.
+fn parse_args(raw: Vec<OsString>) -> std::result::Result<Args, clap::Error> {
+    let args = Args::try_parse_from(&raw)?;
+    if args.command.is_some() {
+        return Ok(args);
+    }
+
+    let before_dashes = raw
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .collect::<Vec<_>>();
+    let command = Args::command();
+    let subcommand = args.new_names.iter().find(|name| {
+        before_dashes.iter().any(|arg| *arg == name.as_str())
+            && command
+                .get_subcommands()
+                .any(|sub| sub.get_name() == *name || sub.get_all_aliases().any(|a| a == *name))
+    });
+    match subcommand {
+        Some(name) => Err(Args::command().error(
+            ErrorKind::ArgumentConflict,
+            format!(
+                "'{}' is a subcommand and must come first; to rename to a file named '{}', use `rn -- {}`",
+                name, name, name
+            ),
+        )),
+        None => Ok(args),
+    }
+}
+
+fn main() {
+    let args = parse_args(std::env::args_os().collect()).unwrap_or_else(|e| e.exit());
+
+    let result = match args.command {
+        Some(Command::Undo { list: true, .. }) => list_undoable(),
+        Some(Command::Undo { n, list: false }) => undo(n),
+        Some(Command::Redo) => redo(),
+        Some(Command::Restore { ref file }) => restore(file),
+        Some(Command::Which {
+            ref new_name,
+            ref select,
+        }) => which(new_name, select),
+        Some(Command::Explain {
+            ref new_name,
+            ref select,
+        }) => explain(new_name, select),
+        Some(Command::Config { ref action }) => {
+            config(action.as_ref().unwrap_or(&ConfigAction::Show))
+        }
+        Some(Command::Plan {
+            ref new_names,
+            ref output,
+            on_conflict,
+            ref select,
+        }) => plan(new_names, output.as_deref(), on_conflict, select),
+        Some(Command::Apply { ref plan }) => apply(plan),
+        None => {
+            // Renames that happened are reported even when a later step fails
+            let mut outcome = Outcome::default();
+            let result = rename(&args, &mut outcome);
+            for report in &outcome.reports {
+                print_report(report, &args);
+            }
+            match result {
+                Ok(()) => return,
+                Err(e) if args.format == Format::Json => {
+                    print_report(&Report::failed(&e, outcome.failed.as_deref()), &args);
+                    std::process::exit(exit_code(&e));
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+
+    match result {
+        Ok(msg) => println!("{}", msg),
+        Err(e) => {
+            match e {
+                Error::TargetExists(_) if args.command.is_none() => eprintln!(
+                    "{} Use --force to overwrite or --on-conflict to choose a strategy.",
+                    e
+                ),
+                _ => eprintln!("{}", e),
+            }
+            std::process::exit(exit_code(&e));
+        }
+    }
+}
+
+fn print_report(report: &Report, args: &Args) {
+    if let Err(e) = report.print(args.format, args.print0) {
+        eprintln!("{}", Error::io("Failed to write output", e));
+        std::process::exit(exit::IO);
+    }
+}
+
+/// What a rename run has to show, even when it fails part way.
+#[derive(Default)]
+struct Outcome {
+    reports: Vec<Report>,
+    /// The new name an error is about, when it is about one
+    failed: Option<PathBuf>,
+}
+
+/// Rename to every name in `args`, inferring all of them before renaming any,
+/// and add a report for each to `outcome`.
+fn rename(args: &Args, outcome: &mut Outcome) -> Result<()> {
+    let config = Config::load()?;
+
+    let on_conflict = if args.force {
+        OnConflict::Overwrite
+    } else {
+        args.on_conflict.or(config.on_conflict).unwrap_or_default()
+    };
+    let pick = (args.pick || config.pick) && !args.no_pick;
+    let xattr = (args.xattr || config.xattr) && !args.no_xattr;
+    let trash = (args.trash || config.trash) && !args.no_trash;
+    let sync = (args.sync || config.sync) && !args.no_sync;
+    let update_symlinks =
+        (args.update_symlinks || args.symlink_tree.is_some() || config.update_symlinks)
+            && !args.no_update_symlinks;
+
+    let stdin = Path::new("-");
+    if args.select.candidates_from.as_deref() == Some(stdin)
+        && (args.new_names.iter().any(|n| n == "-") || args.from_file.as_deref() == Some(stdin))
+    {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "stdin can supply either the new names or the candidates, not both",
+            )
+            .exit();
+    }
+
+    let mut renamer = args
+        .select
+        .renamer(&config, args.print0)?
+        .on_conflict(on_conflict)
+        .trash(trash)
+        .verify(args.verify || config.verify)
+        .update_symlinks(update_symlinks)
+        .symlink_tree(args.symlink_tree.clone().or(config.symlink_tree.clone()));
+    if pick && prompt::is_interactive() {
+        renamer = renamer.chooser(prompt::pick_candidate);
+    }
+
+    let new_names = new_names(args)?;
+    let inferred = renamer
+        .infer_all(new_names.iter().map(String::as_str))
+        .map_err(|(new_name, e)| {
+            outcome.failed = std::path::absolute(new_name).ok();
+            e
+        })?;
+    let mut skipped = Vec::new();
+    let mut renames = Vec::new();
+    for (new_name, rename) in new_names.iter().zip(inferred) {
+        match rename {
+            Some(rename) => renames.push(rename),
+            None => {
+                let requested = std::path::absolute(new_name)
+                    .map_err(|e| Error::io("Failed to get current directory", e))?;
+                let msg = format!("Skipped: '{}' already exists.", file_name(&requested));
+                skipped.push(Report::skipped(&requested, msg));
+            }
+        }
+    }
+
+    // Every answer is collected before the first rename happens
+    if args.interactive {
+        for rename in &renames {
+            if !prompt::confirm(&confirmation(rename, trash))? {
+                return Err(Error::Aborted);
+            }
+        }
+    }
+
+    let renamed = renamer.apply_all(&renames, interrupt::catch())?;
+    outcome.reports.extend(skipped);
+    for (rename, renamed) in renames.iter().zip(&renamed) {
+        let msg = finish(rename, renamed, xattr);
+        outcome.reports.push(Report::renamed(renamed, msg));
+    }
+    if sync {
+        renamed.iter().try_for_each(sync_renamed)?;
+    }
+    Ok(())
+}
+
+/// The names from the command line, with `-` and --from-file expanded.
+fn new_names(args: &Args) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for name in &args.new_names {
+        match name.as_str() {
+            "-" => names.extend(read_names(io::stdin().lock(), args.print0)?),
+            _ => names.push(name.clone()),
+        }
+    }
+
+    if let Some(path) = &args.from_file {
+        names.extend(read_list(path, args.print0)?);
+    }
+    Ok(names)
+}
+
+/// The names listed in `path`, or on stdin for `-`.
+fn read_list(path: &Path, nul: bool) -> Result<Vec<String>> {
+    if path == Path::new("-") {
+        return read_names(io::stdin().lock(), nul);
+    }
+    let file = fs::File::open(path)
+        .map_err(|e| Error::io(format!("Failed to read names from '{}'", path.display()), e))?;
+    read_names(file, nul)
+}
+
+/// Split a list of names on newlines, or on NUL bytes with `nul`. Empty
+/// entries are ignored.
+fn read_names(mut reader: impl Read, nul: bool) -> Result<Vec<String>> {
+    let mut contents = Vec::new();
+    reader
+        .read_to_end(&mut contents)
+        .map_err(|e| Error::io("Failed to read names", e))?;
+
+    let separator = if nul { b'\0' } else { b'\n' };
+    contents
+        .split(|&b| b == separator)
+        .map(|name| match nul {
+            true => name,
+            false => name.strip_suffix(b"\r").unwrap_or(name),
+        })
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            String::from_utf8(name.to_vec())
+                .map_err(|_| Error::InvalidName(String::from_utf8_lossy(name).into_owned()))
+        })
+        .collect()
+}
+
+/// Record a completed rename for undo and provenance, and describe it.
+fn finish(rename: &Rename, renamed: &Renamed, xattr: bool) -> String {
+    // The rename already happened, so a journal failure is only a warning
+    if let Err(e) = Entry::new(renamed).and_then(|entry| Journal::open()?.record(entry)) {
+        eprintln!("Warning: rename not recorded for undo: {}", e);
+    }
+
+    // Provenance is best effort: many filesystems (tmpfs, vfat, NFS) lack user xattrs
+    if xattr
+        && let Some(previous_name) = renamed.source.file_name()
+        && let Err(e) = provenance::record(&renamed.target, previous_name)
+    {
+        eprintln!("Warning: {}", e);
+    }
+
+    let mut msg = format!(
+        "{} → {}",
+        file_name(&renamed.source),
+        file_name(&renamed.target)
+    );
+    if let Some(conflict) = &renamed.conflict {
+        msg.push_str(&format!(
+            " ({})",
+            conflict.describe(&file_name(&rename.requested))
+        ));
+    }
+    msg.push_str(&relinked_lines(&renamed.relinked, false));
+    msg
+}
+
+/// Sync every directory a completed rename touched; only then is it durable.
+fn sync_renamed(renamed: &Renamed) -> Result<()> {
+    renamed.sync().map_err(|e| match e {
+        Error::Io { context, source } => Error::io(
+            format!(
+                "{} → {}, but the rename may not be durable: {}",
+                file_name(&renamed.source),
+                file_name(&renamed.target),
+                context
+            ),
+            source,
+        ),
+        e => e,
+    })
+}
+
+/// Resolve the source for `new_name`, printed the way `new_name` was given
+/// so it can be passed straight to `git mv`, `cp` or an editor.
+fn which(new_name: &str, select: &Selection) -> Result<String> {
+    let resolved = select.renamer(&Config::load()?, false)?.resolve(new_name)?;
+    let source = Path::new(new_name).with_file_name(file_name(&resolved.source));
+
+    // A symlink's target may be elsewhere, and is then printed in full
+    let dir = match source.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match dir.canonicalize().ok().as_deref() == resolved.source.parent() {
+        true => Ok(source.display().to_string()),
+        false => Ok(resolved.source.display().to_string()),
+    }
+}
+
+/// Walk through the inference for `new_name`, under the configured conflict
+/// strategy, without renaming anything.
+fn explain(new_name: &str, select: &Selection) -> Result<String> {
+    let config = Config::load()?;
+    let on_conflict = config.on_conflict.unwrap_or_default();
+    let explanation = select
+        .renamer(&config, false)?
+        .on_conflict(on_conflict)
+        .trash(config.trash)
+        .explain(new_name)?;
+
+    let name = file_name(Path::new(new_name));
+    let mut lines = vec![format!(
+        "Looking for '{}' in {}",
+        name,
+        explanation.directory.display()
+    )];
+    lines.push(match explanation.target_exists {
+        true => format!("Target:     exists, on conflict: {}", on_conflict.name()),
+        false => "Target:     free".to_string(),
+    });
+
+    if explanation.candidates.is_empty() {
+        lines.push(format!(
+            "Candidates: none (no extension change, expansion or reduction of '{}')",
+            name
+        ));
+    } else {
+        lines.push("Candidates, most likely first:".to_string());
+        let width = explanation
+            .candidates
+            .iter()
+            .map(|c| c.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for candidate in &explanation.candidates {
+            lines.push(format!(
+                "  {:<width$}  ({})",
+                candidate,
+                prompt::annotation(candidate, &explanation.candidates),
+                width = width
+            ));
+        }
+    }
+
+    lines.push(match explanation.outcome {
+        Ok(Some(rename)) => format!(
+            "Outcome:    {} → {}",
+            file_name(&rename.source),
+            file_name(&rename.target)
+        ),
+        Ok(None) => format!("Outcome:    skipped, '{}' already exists", name),
+        Err(Error::Ambiguous { candidates, .. })
+            if candidates.iter().all(|c| c.group == candidates[0].group) =>
+        {
+            "Outcome:    refused, ambiguous match of the same data (use --same-data or --pick)"
+                .to_string()
+        }
+        Err(Error::Ambiguous { .. }) => {
+            "Outcome:    refused, ambiguous match (use --pick or a more specific name)".to_string()
+        }
+        Err(e) => format!("Outcome:    refused: {}", e),
+    });
+    Ok(lines.join("\n"))
+}
+
+/// Print or create the config file.
+fn config(action: &ConfigAction) -> Result<String> {
+    let path = config::config_path().ok_or_else(|| {
+        Error::Config(
+            "Cannot locate the config file: none of SNIPREN_CONFIG, XDG_CONFIG_HOME or HOME is set"
+                .to_string(),
+        )
+    })?;
+
+    match action {
+        ConfigAction::Show => {
+            let status = match path.exists() {
+                true => "",
+                false => " (not found, showing defaults)",
+            };
+            Ok(format!(
+                "# {}{}\n{}",
+                path.display(),
+                status,
+                Config::load()?.to_toml()?.trim_end()
+            ))
+        }
+        ConfigAction::Path => Ok(path.display().to_string()),
+        ConfigAction::Init => {
+            config::init(&path)?;
+            Ok(format!("Wrote '{}'.", path.display()))
+        }
+    }
+}
+
+/// Infer renames for `new_names` and write them out as a plan.
+fn plan(
+    new_names: &[String],
+    output: Option<&Path>,
+    on_conflict: Option<OnConflict>,
+    select: &Selection,
+) -> Result<String> {
+    let config = Config::load()?;
+
+    let mut renamer = select
+        .renamer(&config, false)?
+        .on_conflict(on_conflict.or(config.on_conflict).unwrap_or_default());
+    if config.pick && prompt::is_interactive() {
+        renamer = renamer.chooser(prompt::pick_candidate);
+    }
+
+    let plan = renamer.plan(new_names.iter().map(String::as_str))?;
+    let json = plan.to_json()?;
+    let Some(output) = output else {
+        return Ok(json);
+    };
+
+    fs::write(output, json + "\n")
+        .map_err(|e| Error::io(format!("Failed to write plan '{}'", output.display()), e))?;
+
+    let mut msg = format!(
+        "Planned {} rename(s) in '{}':",
+        plan.renames.len(),
+        output.display()
+    );
+    for planned in &plan.renames {
+        msg.push_str(&format!(
+            "\n  {} → {}",
+            planned.rename.source.display(),
+            file_name(&planned.rename.target)
+        ));
+    }
+    Ok(msg)
+}
+
+/// Perform a plan written by `rn plan`, once every rename in it checks out.
+fn apply(path: &Path) -> Result<String> {
+    let config = Config::load()?;
+    let plan = RenamePlan::load(path)?;
+    plan.check()?;
+
+    let renames: Vec<Rename> = plan.renames.iter().map(|p| p.rename.clone()).collect();
+    let renamer = config.renamer()?.trash(config.trash).verify(config.verify);
+    let renamed = renamer.apply_all(&renames, interrupt::catch())?;
+    for (rename, renamed) in renames.iter().zip(&renamed) {
+        println!("{}", finish(rename, renamed, config.xattr));
+    }
+    if config.sync {
+        renamed.iter().try_for_each(sync_renamed)?;
+    }
+
+    Ok(format!(
+        "Applied {} rename(s) from '{}'.",
+        plan.renames.len(),
+        path.display()
+    ))
+}
+
+/// The `-i` question, mentioning what happens to an existing target.
+fn confirmation(rename: &Rename, trash: bool) -> String {
+    let source_name = file_name(&rename.source);
+    let target_name = file_name(&rename.target);
+
+    match (rename.conflict, &rename.backup) {
+        (_, Some(backup)) => format!(
+            "rn: rename '{}' → '{}', keeping the existing '{}' as '{}'?",
+            source_name,
+            target_name,
+            target_name,
+            file_name(backup)
+        ),
+        (Some(OnConflict::Overwrite), _) if trash => format!(
+            "rn: rename '{}' → '{}', moving the existing '{}' to the trash?",
+            source_name, target_name, target_name
+        ),
+        (Some(OnConflict::Overwrite), _) => format!(
+            "rn: rename '{}' → '{}', overwriting the existing '{}'?",
+            source_name, target_name, target_name
+        ),
+        _ => format!("rn: rename '{}' → '{}'?", source_name, target_name),
+    }
+}
+
+fn undo(n: u64) -> Result<String> {
+    let entry = Journal::open()?.undo(n as usize)?;
+    Ok(format!(
+        "{} → {} (undone){}",
+        entry.target.display(),
+        entry.source.display(),
+        relinked_lines(&entry.relinked, true)
+    ))
+}
+
+fn redo() -> Result<String> {
+    let entry = Journal::open()?.redo()?;
+    Ok(format!(
+        "{} → {} (redone){}",
+        entry.source.display(),
+        entry.target.display(),
+        relinked_lines(&entry.relinked, false)
+    ))
+}
+
+/// One indented line per rewritten symlink, showing what it now says, or
+/// what it says again with `reverted`.
+fn relinked_lines(relinked: &[Relinked], reverted: bool) -> String {
+    relinked
+        .iter()
+        .map(|r| {
+            let now = if reverted { &r.old } else { &r.new };
+            format!("\n  {} -> {}", r.link.display(), now.display())
+        })
+        .collect()
+}
+
+/// Rename `file` back to the name recorded in its extended attributes.
+fn restore(file: &Path) -> Result<String> {
+    let current_name = file
+        .file_name()
+        .ok_or_else(|| Error::InvalidName(file.display().to_string()))?;
+    let provenance = provenance::read(file)?.ok_or_else(|| {
+        Error::Stale(format!(
+            "No previous name recorded on '{}'.",
+            file.display()
+        ))
+    })?;
+
+    // Only ever restore within the same directory
+    let previous_name = Path::new(&provenance.previous_name);
+    if previous_name.components().count() != 1 || previous_name.file_name().is_none() {
+        return Err(Error::InvalidName(previous_name.display().to_string()));
+    }
+
+    let target = file.with_file_name(previous_name);
+    if fs::symlink_metadata(&target).is_ok() {
+        return Err(Error::TargetExists(target));
+    }
+
+    fs::rename(file, &target).map_err(|e| Error::io("Failed to rename", e))?;
+
+    // Record the name we are leaving so a second restore swaps back
+    if let Err(e) = provenance::record(&target, current_name) {
+        eprintln!("Warning: {}", e);
+    }
+
+    let mut msg = format!(
+        "{} → {} (restored",
+        file_name(file),
+        previous_name.display()
+    );
+    if let Some(renamed_at) = provenance.renamed_at {
+        msg.push_str(&format!(
+            ", renamed {}",
+            journal::format_timestamp(renamed_at)
+        ));
+    }
+    msg.push(')');
+    Ok(msg)
+}
+
+/// List the most recent renames that can be undone, numbered for `rn undo N`.
+fn list_undoable() -> Result<String> {
+    let journal = Journal::open()?;
+    let lines: Vec<String> = journal
+        .active()
+        .take(20)
+        .enumerate()
+        .map(|(i, e)| {
+            format!(
+                "{:>3}  {}  {} → {}",
+                i + 1,
+                journal::format_timestamp(e.timestamp),
+                e.source.display(),
+                file_name(&e.target)
+            )
+        })
+        .collect();
+
+    if lines.is_empty() {
+        return Ok("Nothing to undo.".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    fn parse(args: &[&str]) -> std::result::Result<Args, clap::Error> {
+        parse_args(args.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn test_parse_args_subcommand_names() {
+        // A subcommand name after a flag is neither run nor renamed to
+        for args in [
+            &["rn", "--sync", "undo"][..],
+            &["rn", "--format", "json", "config"],
+            &["rn", "a_v2.txt", "redo"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+            assert_eq!(err.exit_code(), exit::USAGE);
+            assert!(err.to_string().contains("rn -- "));
+        }
+
+        // `--` makes it a name, and first it is the subcommand
+        let args = parse(&["rn", "--sync", "--", "undo"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.new_names, ["undo"]);
+        assert!(matches!(
+            parse(&["rn", "undo"]).unwrap().command,
+            Some(Command::Undo { .. })
+        ));
+        assert_eq!(
+            parse(&["rn", "--sync", "undone"]).unwrap().new_names,
+            ["undone"]
+        );
+    }
+
+    #[test]
+    fn test_read_names() {
+        let read = |input: &[u8], nul| read_names(input, nul);
+
+        // Lines, with CRLF endings and blank lines dropped
+        assert_eq!(
+            read(b"a_v2.txt\r\n\nb c.txt\n\n", false).unwrap(),
+            ["a_v2.txt", "b c.txt"]
+        );
+        assert_eq!(read(b"last", false).unwrap(), ["last"]);
+        assert!(read(b"", false).unwrap().is_empty());
+
+        // NUL-delimited names keep newlines and carriage returns
+        assert_eq!(
+            read(b"line\nbreak\0cr\r\0\0", true).unwrap(),
+            ["line\nbreak", "cr\r"]
+        );
+
+        assert!(matches!(
+            read(b"ok.txt\n\xff\xfe.txt\n", false),
+            Err(Error::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn test_restore_stays_in_directory() {
+        let dir = scratch("restore");
+        let file = dir.join("notes_v2.txt");
+        fs::write(&file, "").unwrap();
+        if xattr::set(&file, "user.snipren.probe", b"").is_err() {
+            fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+
+        for previous_name in ["../notes.txt", "sub/notes.txt", "/tmp/notes.txt"] {
+            provenance::record(&file, previous_name.as_ref()).unwrap();
+            assert!(matches!(restore(&file), Err(Error::InvalidName(_))));
+            assert!(file.exists());
+        }
+
+        provenance::record(&file, "notes.txt".as_ref()).unwrap();
+        restore(&file).unwrap();
+        assert!(dir.join("notes.txt").exists() && !file.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
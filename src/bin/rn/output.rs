@@ -0,0 +1,259 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use snipren::{Candidate, Error, MatchRule, Relinked, Renamed, Resolution};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// How the outcome of a rename is printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `old → new` for people
+    #[default]
+    Human,
+    /// One JSON object per new name, or one for the error that stopped the
+    /// batch, each on its own line, with stable field names
+    Json,
+}
+
+/// Outcome of a rename. Every field is always present in JSON (as `null` when
+/// it does not apply), so scripts can rely on the shape.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// `renamed`, `skipped` or `error`
+    pub status: &'static str,
+    /// Name of the file before the rename
+    pub source: Option<String>,
+    /// Name of the file after the rename, or the name that was skipped
+    pub target: Option<String>,
+    /// Absolute directory both names are in
+    pub directory: Option<String>,
+    /// Rule that matched the source to the new name
+    pub rule: Option<MatchRule>,
+    /// How an existing target was dealt with
+    pub conflict: Option<Resolution>,
+    /// Symlinks rewritten to follow the rename
+    pub relinked: Vec<Relinked>,
+    pub error: Option<ErrorReport>,
+    /// The human readable form of the report
+    #[serde(skip)]
+    pub message: String,
+    /// Absolute path of the renamed file, for `--print0`
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    /// Stable name of the error, see `snipren::Error::kind`
+    pub kind: &'static str,
+    pub message: String,
+    /// Matching files, most likely first, when the match was ambiguous
+    pub candidates: Vec<Candidate>,
+}
+
+impl Report {
+    pub fn renamed(renamed: &Renamed, message: String) -> Report {
+        Report {
+            status: "renamed",
+            source: Some(name(&renamed.source)),
+            target: Some(name(&renamed.target)),
+            directory: renamed.target.parent().map(|d| d.display().to_string()),
+            rule: Some(renamed.rule),
+            conflict: renamed.conflict.clone(),
+            relinked: renamed.relinked.clone(),
+            error: None,
+            message,
+            path: Some(renamed.target.clone()),
+        }
+    }
+
+    pub fn skipped(requested: &Path, message: String) -> Report {
+        Report {
+            status: "skipped",
+            target: Some(name(requested)),
+            directory: requested.parent().map(|d| d.display().to_string()),
+            message,
+            ..Report::empty()
+        }
+    }
+
+    /// An error, about the new name `requested` when it concerns one.
+    pub fn failed(error: &Error, requested: Option<&Path>) -> Report {
+        let candidates = match error {
+            Error::Ambiguous { candidates, .. } => candidates.clone(),
+            _ => Vec::new(),
+        };
+        Report {
+            status: "error",
+            target: requested.map(name),
+            directory: requested
+                .and_then(Path::parent)
+                .map(|d| d.display().to_string()),
+            error: Some(ErrorReport {
+                kind: error.kind(),
+                message: error.to_string(),
+                candidates,
+            }),
+            message: error.to_string(),
+            ..Report::empty()
+        }
+    }
+
+    fn empty() -> Report {
+        Report {
+            status: "",
+            source: None,
+            target: None,
+            directory: None,
+            rule: None,
+            conflict: None,
+            relinked: Vec::new(),
+            error: None,
+            message: String::new(),
+            path: None,
+        }
+    }
+
+    /// Print the report to stdout in the chosen format.
+    ///
+    /// With `print0` only the path of a renamed file is written, terminated
+    /// by a NUL byte, for `xargs -0`; skips and errors print nothing there.
+    pub fn print(&self, format: Format, print0: bool) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        if print0 {
+            if let Some(path) = &self.path {
+                stdout.write_all(path.as_os_str().as_bytes())?;
+                stdout.write_all(b"\0")?;
+            }
+            return stdout.flush();
+        }
+
+        match format {
+            Format::Human => writeln!(stdout, "{}", self.message),
+            Format::Json => {
+                serde_json::to_writer(&mut stdout, self)?;
+                writeln!(stdout)
+            }
+        }
+    }
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Report;
+    use serde_json::json;
+    use snipren::{Candidate, Error, FileKind, MatchRule, OnConflict, Renamed, Resolution};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_report_renamed_fields() {
+        let renamed = Renamed {
+            source: PathBuf::from("/data/report.csv"),
+            target: PathBuf::from("/data/report_v2.csv"),
+            rule: MatchRule::Expansion,
+            conflict: Some(Resolution {
+                strategy: OnConflict::Backup,
+                backup: Some(PathBuf::from("/data/report_v2.csv~")),
+                trashed: None,
+            }),
+            relinked: Vec::new(),
+        };
+        let report = Report::renamed(&renamed, String::new());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "status": "renamed",
+                "source": "report.csv",
+                "target": "report_v2.csv",
+                "directory": "/data",
+                "rule": "expansion",
+                "conflict": {
+                    "strategy": "backup",
+                    "backup": "/data/report_v2.csv~",
+                    "trashed": null
+                },
+                "relinked": [],
+                "error": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_report_skipped_fields() {
+        let report = Report::skipped(Path::new("/data/report_v2.csv"), String::new());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "status": "skipped",
+                "source": null,
+                "target": "report_v2.csv",
+                "directory": "/data",
+                "rule": null,
+                "conflict": null,
+                "relinked": [],
+                "error": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_report_error_fields() {
+        let candidate = |name: &str, group| Candidate {
+            name: name.to_string(),
+            rule: MatchRule::ExtensionChange,
+            path: Path::new("/data").join(name),
+            kind: FileKind::File,
+            group,
+        };
+        let error = Error::Ambiguous {
+            name: "report.json".to_string(),
+            candidates: vec![candidate("report.csv", 0), candidate("report.txt", 1)],
+        };
+        let report = Report::failed(&error, Some(Path::new("/data/report.json")));
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "status": "error",
+                "source": null,
+                "target": "report.json",
+                "directory": "/data",
+                "rule": null,
+                "conflict": null,
+                "relinked": [],
+                "error": {
+                    "kind": "ambiguous",
+                    "message": error.to_string(),
+                    "candidates": [
+                        {
+                            "name": "report.csv",
+                            "rule": "extension_change",
+                            "path": "/data/report.csv",
+                            "kind": "file",
+                            "group": 0
+                        },
+                        {
+                            "name": "report.txt",
+                            "rule": "extension_change",
+                            "path": "/data/report.txt",
+                            "kind": "file",
+                            "group": 1
+                        }
+                    ]
+                }
+            })
+        );
+
+        // An error about the whole batch names no file
+        let report = serde_json::to_value(Report::failed(&Error::Aborted, None)).unwrap();
+        assert_eq!(report["target"], json!(null));
+        assert_eq!(report["directory"], json!(null));
+        assert_eq!(report["error"]["kind"], "aborted");
+    }
+}
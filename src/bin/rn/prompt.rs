@@ -0,0 +1,111 @@
+use snipren::{Candidate, Error, FileKind, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// True when both stdin and stdout are attached to a terminal.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// The rule a candidate matched by, its kind unless it is a plain file, and
+/// which earlier candidate in `candidates` holds the same data, if any.
+pub fn annotation(candidate: &Candidate, candidates: &[Candidate]) -> String {
+    let mut note = match candidate.kind {
+        FileKind::File => candidate.rule.to_string(),
+        kind => format!("{}, {}", candidate.rule, kind.name()),
+    };
+    if let Some(first) = candidates.iter().find(|c| c.group == candidate.group)
+        && first != candidate
+    {
+        note.push_str(&format!(", same data as {}", first.name));
+    }
+    note
+}
+
+/// Ask the user to choose one of several ranked candidates.
+///
+/// Candidates are listed on stderr so stdout stays reserved for the result.
+/// Returns `None` when the user aborts (by `q` or end of input).
+pub fn pick_candidate(new_filename: &str, candidates: &[Candidate]) -> Result<Option<usize>> {
+    let mut stderr = io::stderr().lock();
+    let width = candidates
+        .iter()
+        .map(|c| c.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut menu = format!("Multiple candidates found for '{}':\n", new_filename);
+    for (i, candidate) in candidates.iter().enumerate() {
+        menu.push_str(&format!(
+            "  {}) {:width$}  ({})\n",
+            i + 1,
+            candidate,
+            annotation(candidate, candidates),
+            width = width
+        ));
+    }
+    write!(stderr, "{}", menu).map_err(|e| Error::io("Failed to write prompt", e))?;
+
+    let stdin = io::stdin();
+    loop {
+        write!(
+            stderr,
+            "Select a file to rename [1-{}, q to abort]: ",
+            candidates.len()
+        )
+        .and_then(|_| stderr.flush())
+        .map_err(|e| Error::io("Failed to write prompt", e))?;
+
+        let mut line = String::new();
+        let read = stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| Error::io("Failed to read selection", e))?;
+        if read == 0 {
+            // End of input is treated as an abort
+            let _ = writeln!(stderr);
+            return Ok(None);
+        }
+
+        let answer = line.trim();
+        if answer.eq_ignore_ascii_case("q") || answer.eq_ignore_ascii_case("quit") {
+            return Ok(None);
+        }
+
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => return Ok(Some(n - 1)),
+            _ => {
+                let _ = writeln!(stderr, "Invalid selection '{}'.", answer);
+            }
+        }
+    }
+}
+
+/// Ask a yes/no question on stderr and read the answer from stdin.
+///
+/// Refuses rather than blocking when stdin is not a terminal, so a piped or
+/// scripted invocation never hangs waiting for an answer.
+pub fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(Error::NotPermitted(
+            "cannot ask for confirmation, stdin is not a terminal.".to_string(),
+        ));
+    }
+
+    let mut stderr = io::stderr().lock();
+    write!(stderr, "{} [y/N] ", question)
+        .and_then(|_| stderr.flush())
+        .map_err(|e| Error::io("Failed to write prompt", e))?;
+
+    let mut line = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| Error::io("Failed to read answer", e))?;
+    if read == 0 {
+        let _ = writeln!(stderr);
+        return Ok(false);
+    }
+
+    let answer = line.trim();
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
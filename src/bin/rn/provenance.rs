@@ -0,0 +1,102 @@
+use snipren::{Error, Result};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Extended attribute holding the file's name before its last rename.
+const PREVIOUS_NAME: &str = "user.snipren.previous_name";
+/// Extended attribute holding when the last rename happened (seconds since the epoch).
+const RENAMED_AT: &str = "user.snipren.renamed_at";
+
+/// Provenance stored on a renamed file.
+pub struct Provenance {
+    pub previous_name: OsString,
+    pub renamed_at: Option<u64>,
+}
+
+/// Store `previous_name` and the current time on the file at `path`.
+///
+/// Operates on symlinks themselves, not their targets.
+pub fn record(path: &Path, previous_name: &OsStr) -> Result<()> {
+    let fail = |e: io::Error| {
+        Error::io(
+            format!("Failed to store the previous name on '{}'", path.display()),
+            e,
+        )
+    };
+    if !xattr::SUPPORTED_PLATFORM {
+        return Err(fail(unsupported()));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    xattr::set(path, PREVIOUS_NAME, previous_name.as_bytes())
+        .and_then(|_| xattr::set(path, RENAMED_AT, now.to_string().as_bytes()))
+        .map_err(fail)
+}
+
+/// Read the provenance of the file at `path`, if any was recorded.
+pub fn read(path: &Path) -> Result<Option<Provenance>> {
+    let fail = |e: io::Error| {
+        Error::io(
+            format!("Failed to read extended attributes of '{}'", path.display()),
+            e,
+        )
+    };
+    if !xattr::SUPPORTED_PLATFORM {
+        return Err(fail(unsupported()));
+    }
+
+    let Some(previous_name) = xattr::get(path, PREVIOUS_NAME).map_err(fail)? else {
+        return Ok(None);
+    };
+    let renamed_at = xattr::get(path, RENAMED_AT)
+        .map_err(fail)?
+        .and_then(|v| String::from_utf8(v).ok())
+        .and_then(|v| v.parse().ok());
+
+    Ok(Some(Provenance {
+        previous_name: OsString::from_vec(previous_name),
+        renamed_at,
+    }))
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported on this platform",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, record};
+    use crate::testing::scratch;
+    use std::ffi::OsStr;
+    use std::fs;
+
+    #[test]
+    fn test_provenance_round_trip() {
+        let dir = scratch("provenance");
+        let file = dir.join("report_v2.csv");
+        fs::write(&file, "").unwrap();
+
+        // Not every filesystem holding the temp dir stores user attributes
+        if xattr::set(&file, "user.snipren.probe", b"").is_err() {
+            fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+
+        assert!(read(&file).unwrap().is_none());
+        record(&file, OsStr::new("report.csv")).unwrap();
+        let provenance = read(&file).unwrap().unwrap();
+        assert_eq!(provenance.previous_name, "report.csv");
+        assert!(provenance.renamed_at.is_some_and(|at| at > 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
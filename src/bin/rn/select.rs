@@ -0,0 +1,144 @@
+use snipren::{Glob, Renamer, Result, SameData, SymlinkPolicy};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::filters::{self, Filters};
+
+/// Flags deciding which files are candidates, shared by renaming and the
+/// subcommands that must match the same way (`which`, `explain`, `plan`).
+#[derive(clap::Args, Debug)]
+pub struct Selection {
+    /// Only consider the files listed in FILE (`-` for stdin), one per line,
+    /// instead of the directory listing; each is renamed where it is
+    #[arg(long, value_name = "FILE")]
+    pub candidates_from: Option<PathBuf>,
+
+    /// Also search the subdirectories of the target directory; a match is
+    /// renamed within its own directory
+    #[arg(short, long, conflicts_with = "candidates_from")]
+    pub recursive: bool,
+
+    /// Consider directories as well as files
+    #[arg(long, overrides_with = "no_dirs")]
+    pub dirs: bool,
+
+    /// Only consider files, even if directories are enabled in the config
+    #[arg(long, overrides_with = "dirs")]
+    pub no_dirs: bool,
+
+    /// What to do with symlinks: leave them out, rename the link, or rename
+    /// the file it points to
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub symlinks: Option<SymlinkPolicy>,
+
+    /// Consider FIFOs, sockets and devices as well
+    #[arg(long, overrides_with = "no_special")]
+    pub special: bool,
+
+    /// Leave out FIFOs, sockets and devices, even if enabled in the config
+    #[arg(long, overrides_with = "special")]
+    pub no_special: bool,
+
+    /// Which name to rename when all candidates hold the same data:
+    /// refuse, best, oldest or newest
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub same_data: Option<SameData>,
+
+    /// Treat byte-identical files as the same data, not only hardlinks
+    #[arg(long, overrides_with = "no_compare_contents")]
+    pub compare_contents: bool,
+
+    /// Only treat hardlinks as the same data, even if enabled in the config
+    #[arg(long, overrides_with = "compare_contents")]
+    pub no_compare_contents: bool,
+
+    /// Only consider names matching GLOB; may be repeated
+    #[arg(long, value_name = "GLOB", value_parser = filters::parse_glob)]
+    pub include: Vec<Glob>,
+
+    /// Never consider names matching GLOB; may be repeated
+    #[arg(long, value_name = "GLOB", value_parser = filters::parse_glob)]
+    pub exclude: Vec<Glob>,
+
+    /// Consider hidden files even when the new name is not hidden
+    #[arg(short = 'H', long, overrides_with = "no_hidden")]
+    pub hidden: bool,
+
+    /// Skip hidden files, even if enabled in the config
+    #[arg(long, overrides_with = "hidden")]
+    pub no_hidden: bool,
+
+    /// Leave out what .gitignore, .ignore and git's global excludes ignore
+    /// (default), even if disabled in the config
+    #[arg(long, overrides_with = "no_ignore")]
+    pub ignore: bool,
+
+    /// Consider files that ignore files leave out
+    #[arg(long, overrides_with = "ignore")]
+    pub no_ignore: bool,
+
+    /// Only consider entries modified less than AGE ago, e.g. 30m, 12h, 2d
+    #[arg(long, value_name = "AGE", value_parser = filters::parse_duration)]
+    pub newer_than: Option<Duration>,
+
+    /// Only consider entries modified more than AGE ago
+    #[arg(long, value_name = "AGE", value_parser = filters::parse_duration)]
+    pub older_than: Option<Duration>,
+
+    /// Only consider files of at least SIZE, e.g. 512, 10K, 5M
+    #[arg(long, value_name = "SIZE", value_parser = filters::parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only consider files of at most SIZE
+    #[arg(long, value_name = "SIZE", value_parser = filters::parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Descend at most N levels with --recursive, where 1 is the target
+    /// directory alone
+    #[arg(long, value_name = "N", requires = "recursive", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_depth: Option<u64>,
+}
+
+impl Selection {
+    /// A renamer choosing candidates as these flags and `config` say, flags
+    /// taking precedence. A candidate list is read NUL-delimited with `nul`.
+    pub fn renamer(&self, config: &Config, nul: bool) -> Result<Renamer> {
+        // Each filter given on the command line replaces the configured one
+        let configured = config.filters()?;
+        let filters = Filters {
+            include: match self.include.is_empty() {
+                true => configured.include,
+                false => self.include.clone(),
+            },
+            exclude: match self.exclude.is_empty() {
+                true => configured.exclude,
+                false => self.exclude.clone(),
+            },
+            newer_than: self.newer_than.or(configured.newer_than),
+            older_than: self.older_than.or(configured.older_than),
+            min_size: self.min_size.or(configured.min_size),
+            max_size: self.max_size.or(configured.max_size),
+        };
+
+        let mut renamer = config
+            .renamer_with(filters)
+            .dirs((self.dirs || config.dirs) && !self.no_dirs)
+            .symlinks(self.symlinks.or(config.symlinks).unwrap_or_default())
+            .special((self.special || config.special) && !self.no_special)
+            .hidden((self.hidden || config.hidden) && !self.no_hidden)
+            .ignore_files((self.ignore || config.ignore_files.unwrap_or(true)) && !self.no_ignore)
+            .same_data(self.same_data.or(config.same_data).unwrap_or_default())
+            .compare_contents(
+                (self.compare_contents || config.compare_contents) && !self.no_compare_contents,
+            );
+        if let Some(path) = &self.candidates_from {
+            let listed = crate::read_list(path, nul)?;
+            renamer = renamer.candidates_from(listed.into_iter().map(PathBuf::from));
+        }
+        if self.recursive {
+            renamer = renamer.recursive(self.max_depth.map(|depth| depth as usize));
+        }
+        Ok(renamer)
+    }
+}
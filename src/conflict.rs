@@ -0,0 +1,217 @@
+use crate::{Error, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to do when the target name is already taken.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Refuse to rename (default)
+    #[default]
+    Refuse,
+    /// Replace the existing target
+    Overwrite,
+    /// Keep the existing target as `name~`
+    Backup,
+    /// Keep the existing target as `name.~N~`, like `mv --backup=numbered`
+    Numbered,
+    /// Rename to `name (N).ext` instead, leaving the existing target alone
+    Suffix,
+    /// Leave everything as it is and report the skip
+    Skip,
+}
+
+impl OnConflict {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OnConflict::Refuse => "refuse",
+            OnConflict::Overwrite => "overwrite",
+            OnConflict::Backup => "backup",
+            OnConflict::Numbered => "numbered",
+            OnConflict::Suffix => "suffix",
+            OnConflict::Skip => "skip",
+        }
+    }
+}
+
+/// How an existing target was dealt with during a rename.
+#[derive(Debug, Clone, Serialize)]
+pub struct Resolution {
+    pub strategy: OnConflict,
+    /// Where the displaced target was moved to, for `backup` and `numbered`
+    pub backup: Option<PathBuf>,
+    /// Where an overwritten target went in the trash, when trashing is enabled
+    pub trashed: Option<PathBuf>,
+}
+
+impl Resolution {
+    /// Short note appended to the rename output.
+    pub fn describe(&self, existing: &str) -> String {
+        match &self.backup {
+            Some(backup) => format!(
+                "{}: '{}' kept as '{}'",
+                self.strategy.name(),
+                existing,
+                backup
+                    .file_name()
+                    .map(|n| n.to_string_lossy())
+                    .unwrap_or_default()
+            ),
+            None => match self.strategy {
+                OnConflict::Overwrite if self.trashed.is_some() => {
+                    format!("overwrite: '{}' moved to the trash", existing)
+                }
+                OnConflict::Overwrite => format!("overwrite: replaced '{}'", existing),
+                OnConflict::Suffix => format!("suffix: '{}' already exists", existing),
+                _ => self.strategy.name().to_string(),
+            },
+        }
+    }
+}
+
+/// Simple backup name: `name~`.
+pub fn backup_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push("~");
+    target.with_file_name(name)
+}
+
+/// Next numbered backup name: `name.~N~`, one past the highest existing N.
+pub fn numbered_backup_path(target: &Path) -> Result<PathBuf> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::InvalidName(target.display().to_string()))?;
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let prefix = format!("{}.~", name);
+
+    let entries = fs::read_dir(dir).map_err(|e| Error::io("Failed to read directory", e))?;
+    let highest = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let entry_name = entry.file_name();
+            let n = entry_name
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .strip_suffix('~')?
+                .parse::<u64>()
+                .ok()?;
+            Some(n)
+        })
+        .max()
+        .unwrap_or(0);
+
+    Ok(target.with_file_name(format!("{}{}~", prefix, highest + 1)))
+}
+
+/// First free `stem (N).ext` variant of `target`.
+pub fn suffixed_path(target: &Path) -> Result<PathBuf> {
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::InvalidName(target.display().to_string()))?;
+
+    // A leading dot marks a hidden file, not an extension
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    (1..=u16::MAX)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .ok_or_else(|| Error::TargetExists(target.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_path, numbered_backup_path, suffixed_path};
+    use crate::testing::scratch;
+    use std::fs;
+    use std::path::Path;
+
+    fn touch(dir: &Path, names: &[&str]) {
+        for name in names {
+            fs::write(dir.join(name), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_conflict_backup_path() {
+        assert_eq!(
+            backup_path(Path::new("/a/notes.txt")),
+            Path::new("/a/notes.txt~")
+        );
+    }
+
+    #[test]
+    fn test_conflict_numbered_backup_path() {
+        let dir = scratch("numbered");
+        let target = dir.join("notes.txt");
+        assert_eq!(
+            numbered_backup_path(&target).unwrap(),
+            dir.join("notes.txt.~1~")
+        );
+
+        // One past the highest, not the first gap
+        touch(&dir, &["notes.txt", "notes.txt.~1~", "notes.txt.~7~"]);
+        assert_eq!(
+            numbered_backup_path(&target).unwrap(),
+            dir.join("notes.txt.~8~")
+        );
+
+        // Lookalikes that are not numbered backups of this name do not count
+        touch(
+            &dir,
+            &[
+                "notes.txt.~x~",
+                "notes.txt.~12",
+                "notes.txt.~-3~",
+                "other.txt.~20~",
+                "notes.txt.~99~.bak",
+            ],
+        );
+        assert_eq!(
+            numbered_backup_path(&target).unwrap(),
+            dir.join("notes.txt.~8~")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_suffixed_path() {
+        let dir = scratch("suffixed");
+        touch(
+            &dir,
+            &[
+                "report.csv",
+                "report (1).csv",
+                "Makefile",
+                ".env",
+                "a.tar.gz",
+            ],
+        );
+
+        assert_eq!(
+            suffixed_path(&dir.join("report.csv")).unwrap(),
+            dir.join("report (2).csv")
+        );
+        assert_eq!(
+            suffixed_path(&dir.join("Makefile")).unwrap(),
+            dir.join("Makefile (1)")
+        );
+        // A dotfile has no extension to keep
+        assert_eq!(
+            suffixed_path(&dir.join(".env")).unwrap(),
+            dir.join(".env (1)")
+        );
+        // Only the last extension stays at the end
+        assert_eq!(
+            suffixed_path(&dir.join("a.tar.gz")).unwrap(),
+            dir.join("a.tar (1).gz")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
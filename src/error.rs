@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{FileKind, MatchRule};
+
+/// A file that matched a requested new name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Candidate {
+    /// The existing filename, or its path relative to the searched directory
+    /// when it lies below it
+    pub name: String,
+    /// The rule under which it matched
+    pub rule: MatchRule,
+    /// Where the file is; for a symlink resolved to its target, where the
+    /// target is
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// Candidates in the same group hold the same data: hardlinks of one
+    /// file, or identical copies when contents are compared
+    pub group: usize,
+}
+
+impl fmt::Display for Candidate {
+    /// The name with its kind indicator, e.g. `project/` for a directory, and
+    /// where it leads when it is a symlink resolved to its target.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.ends_with(&self.name) {
+            true => f.pad(&format!("{}{}", self.name, self.kind.indicator())),
+            false => f.pad(&format!(
+                "{} -> {}{}",
+                self.name,
+                self.path.display(),
+                self.kind.indicator()
+            )),
+        }
+    }
+}
+
+/// Everything that can stop a rename, one variant per distinct outcome.
+#[derive(Debug)]
+pub enum Error {
+    /// The requested name (or a recorded one) is not a usable filename
+    InvalidName(String),
+    /// No file matched the requested name
+    NoMatch(String),
+    /// More than one file matched the requested name
+    Ambiguous {
+        name: String,
+        /// The matching files, most likely intent first
+        candidates: Vec<Candidate>,
+    },
+    /// The target name is taken and the conflict strategy refuses
+    TargetExists(PathBuf),
+    /// A pre-flight check found the rename cannot be performed
+    NotPermitted(String),
+    /// The user declined a prompt
+    Aborted,
+    /// Recorded state (journal, provenance) no longer matches the filesystem,
+    /// or there is nothing recorded to act on
+    Stale(String),
+    /// The configuration file could not be used
+    Config(String),
+    /// An I/O operation failed
+    Io { context: String, source: io::Error },
+}
+
+impl Error {
+    /// Stable, machine-readable name of the variant, e.g. `no_match`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::InvalidName(_) => "invalid_name",
+            Error::NoMatch(_) => "no_match",
+            Error::Ambiguous { .. } => "ambiguous",
+            Error::TargetExists(_) => "target_exists",
+            Error::NotPermitted(_) => "not_permitted",
+            Error::Aborted => "aborted",
+            Error::Stale(_) => "stale",
+            Error::Config(_) => "config",
+            Error::Io { .. } => "io",
+        }
+    }
+
+    /// Wrap an I/O error with a description of what was being attempted.
+    pub fn io(context: impl Into<String>, source: io::Error) -> Error {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "Invalid filename: '{}'", name),
+            Error::NoMatch(name) => write!(f, "No matching files found for '{}'", name),
+            Error::Ambiguous { name, candidates } => {
+                writeln!(f, "Multiple candidates found for '{}':", name)?;
+                for candidate in candidates {
+                    match candidates.iter().find(|c| c.group == candidate.group) {
+                        Some(first) if first != candidate => {
+                            writeln!(f, "  {}  (same data as {})", candidate, first.name)?
+                        }
+                        _ => writeln!(f, "  {}", candidate)?,
+                    }
+                }
+                if candidates.iter().all(|c| c.group == candidates[0].group) {
+                    write!(f, "\nThey all hold the same data.")?;
+                }
+                write!(f, "\nCannot proceed - ambiguous match.")
+            }
+            Error::TargetExists(path) => write!(
+                f,
+                "Target '{}' already exists.",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            Error::NotPermitted(reason) => write!(f, "Cannot rename: {}", reason),
+            Error::Aborted => write!(f, "Aborted - no file renamed."),
+            Error::Stale(reason) => write!(f, "{}", reason),
+            Error::Config(reason) => write!(f, "{}", reason),
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Result type used throughout the library.
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Candidate, Error};
+    use crate::{FileKind, MatchRule};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_ambiguous_lists_candidates() {
+        let err = Error::Ambiguous {
+            name: "report.json".to_string(),
+            candidates: vec![
+                Candidate {
+                    name: "report.csv".to_string(),
+                    rule: MatchRule::ExtensionChange,
+                    path: PathBuf::from("/data/report.csv"),
+                    kind: FileKind::File,
+                    group: 0,
+                },
+                Candidate {
+                    name: "report.txt".to_string(),
+                    rule: MatchRule::ExtensionChange,
+                    path: PathBuf::from("/data/report.txt"),
+                    kind: FileKind::File,
+                    group: 1,
+                },
+            ],
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "Multiple candidates found for 'report.json':\n  report.csv\n  report.txt\n\nCannot proceed - ambiguous match."
+        );
+    }
+
+    #[test]
+    fn test_io_keeps_source() {
+        let err = Error::io(
+            "Failed to read directory",
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        );
+
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(err.to_string().starts_with("Failed to read directory: "));
+        assert_eq!(err.kind(), "io");
+    }
+}
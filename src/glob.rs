@@ -0,0 +1,122 @@
+use regex::Regex;
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// A shell-style pattern for narrowing candidates, like `*.log` or
+/// `build/**`.
+///
+/// `*` and `?` stay within one path component, `**` spans any number of
+/// them, and `[abc]`, `[a-z]` and `[!abc]` match one character from a set.
+/// A pattern without `/` is matched against the file name; one with `/`
+/// against the end of the path, starting at a component boundary.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    anchored: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob> {
+        Glob::build(pattern, false)
+    }
+
+    /// A pattern matched against the whole of a relative path, as an ignore
+    /// file pattern with a `/` is.
+    pub(crate) fn anchored(pattern: &str) -> Result<Glob> {
+        Glob::build(pattern, true)
+    }
+
+    fn build(pattern: &str, anchored: bool) -> Result<Glob> {
+        let invalid = || Error::Config(format!("Invalid pattern '{}'", pattern));
+
+        let mut regex = String::from(if anchored { "^" } else { "(?:^|/)" });
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '[' => {
+                    regex.push('[');
+                    if chars.peek() == Some(&'!') {
+                        chars.next();
+                        regex.push('^');
+                    }
+                    loop {
+                        match chars.next().ok_or_else(invalid)? {
+                            ']' => break,
+                            c @ ('\\' | '[' | '&' | '~') => {
+                                regex.push('\\');
+                                regex.push(c);
+                            }
+                            c => regex.push(c),
+                        }
+                    }
+                    regex.push(']');
+                }
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex).map_err(|_| invalid())?,
+            anchored,
+        })
+    }
+
+    /// Whether `path` matches: its file name, or its trailing components
+    /// when the pattern has a `/`, or all of it when anchored.
+    pub fn matches(&self, path: &Path) -> bool {
+        let subject = match self.anchored || self.pattern.contains('/') {
+            true => path.to_string_lossy(),
+            false => path.file_name().unwrap_or_default().to_string_lossy(),
+        };
+        self.regex.is_match(&subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glob;
+    use std::path::Path;
+
+    #[test]
+    fn test_glob_matches_names_and_paths() {
+        let matches =
+            |pattern: &str, path: &str| Glob::new(pattern).unwrap().matches(Path::new(path));
+
+        assert!(matches("*.log", "/work/build.log"));
+        assert!(!matches("*.log", "/work/build.log.gz"));
+        assert!(matches("report_?.csv", "/work/report_1.csv"));
+        assert!(matches("[!.]*~", "/work/notes.txt~"));
+        assert!(!matches("[!.]*~", "/work/.notes.txt~"));
+        assert!(matches("*.[oa]", "/work/main.o"));
+
+        // With a slash, the pattern anchors at a component boundary
+        assert!(matches("target/**", "/work/target/debug/rn"));
+        assert!(!matches("target/**", "/work/mytarget/rn"));
+        assert!(matches("src/**/*.rs", "/work/src/bin/rn/main.rs"));
+        assert!(matches("src/**/*.rs", "/work/src/lib.rs"));
+
+        // Anchored, the whole path has to match
+        let anchored =
+            |pattern: &str, path: &str| Glob::anchored(pattern).unwrap().matches(Path::new(path));
+        assert!(anchored("target", "target"));
+        assert!(!anchored("target", "src/target"));
+        assert!(anchored("**/target", "src/target"));
+
+        assert!(Glob::new("[abc").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::{Candidate, Error, Result};
+
+/// Which name to rename when every candidate holds the same data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameData {
+    /// Report the match as ambiguous, noting the data is the same (default)
+    #[default]
+    Refuse,
+    /// Rename the most likely candidate
+    Best,
+    /// Rename the candidate modified longest ago
+    Oldest,
+    /// Rename the candidate modified most recently
+    Newest,
+}
+
+impl SameData {
+    /// The candidate to rename under this policy, if any.
+    pub(crate) fn pick(&self, candidates: &[Candidate]) -> Option<usize> {
+        let mtime = |i: &usize| {
+            fs::symlink_metadata(&candidates[*i].path)
+                .map(|m| (m.mtime(), m.mtime_nsec()))
+                .unwrap_or_default()
+        };
+        match self {
+            SameData::Refuse => None,
+            SameData::Best => Some(0),
+            SameData::Oldest => (0..candidates.len()).min_by_key(mtime),
+            // The most likely candidate wins a tie
+            SameData::Newest => (0..candidates.len()).rev().max_by_key(mtime),
+        }
+    }
+}
+
+/// Number the candidates so that those holding the same data share a group:
+/// hardlinks of one inode, and with `contents` also byte-identical files.
+pub(crate) fn group(candidates: &mut [Candidate], contents: bool) -> Result<()> {
+    let metadata = candidates
+        .iter()
+        .map(|c| fs::symlink_metadata(&c.path).ok())
+        .collect::<Vec<_>>();
+
+    for i in 0..candidates.len() {
+        candidates[i].group = i;
+        let Some(this) = &metadata[i] else {
+            continue;
+        };
+        for j in 0..i {
+            let Some(other) = &metadata[j] else {
+                continue;
+            };
+            let same = (this.dev(), this.ino()) == (other.dev(), other.ino())
+                || (contents
+                    && this.is_file()
+                    && other.is_file()
+                    && this.len() == other.len()
+                    && same_contents(&candidates[i].path, &candidates[j].path)?);
+            if same {
+                candidates[i].group = candidates[j].group;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether two files hold exactly the same bytes.
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    let open = |path: &Path| {
+        File::open(path).map_err(|e| Error::io(format!("Failed to read '{}'", path.display()), e))
+    };
+    let (mut a_file, mut b_file) = (open(a)?, open(b)?);
+
+    let mut a_buf = vec![0u8; 64 * 1024];
+    let mut b_buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = read_full(&mut a_file, &mut a_buf)
+            .map_err(|e| Error::io(format!("Failed to read '{}'", a.display()), e))?;
+        let m = read_full(&mut b_file, &mut b_buf)
+            .map_err(|e| Error::io(format!("Failed to read '{}'", b.display()), e))?;
+        if a_buf[..n] != b_buf[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buf` as far as the file allows, so two reads line up.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
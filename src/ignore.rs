@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::Glob;
+
+/// Ignore files read in every directory, later ones taking precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// One pattern line of an ignore file.
+struct Rule {
+    /// The directory of the file the pattern is from, which it is relative to
+    base: PathBuf,
+    glob: Glob,
+    /// `!pattern`: re-include what an earlier pattern ignored
+    negated: bool,
+    /// `pattern/`: only match directories
+    dir_only: bool,
+}
+
+impl Rule {
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        path.strip_prefix(&self.base)
+            .is_ok_and(|relative| self.glob.matches(relative))
+    }
+}
+
+/// The ignore files that apply below a searched directory, the way git and
+/// ripgrep read them.
+///
+/// Inside a git work tree that is the global excludes file, then
+/// `.git/info/exclude`, then the `.gitignore` and `.ignore` files of every
+/// directory from the top of the work tree down. Outside one only `.ignore`
+/// files count, from the searched directory down.
+pub(crate) struct Ignores {
+    /// The work tree, or the searched directory outside one
+    root: PathBuf,
+    git: bool,
+    /// Patterns that apply everywhere below `root`
+    global: Vec<Rule>,
+    /// The patterns of each directory's own ignore files
+    dirs: HashMap<PathBuf, Rc<Vec<Rule>>>,
+}
+
+impl Ignores {
+    pub(crate) fn new(dir: &Path) -> Ignores {
+        let work_tree = dir.ancestors().find(|d| d.join(".git").exists());
+        let root = work_tree.unwrap_or(dir).to_path_buf();
+
+        let mut global = Vec::new();
+        if work_tree.is_some() {
+            let git_dir = root.join(".git");
+            if let Some(file) = excludes_file(&git_dir) {
+                global.extend(read(&root, &file));
+            }
+            global.extend(read(&root, &git_dir.join("info/exclude")));
+        }
+
+        Ignores {
+            root,
+            git: work_tree.is_some(),
+            global,
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Whether the last pattern matching `path` ignores it.
+    ///
+    /// The directories above `path` are not checked: nothing inside an
+    /// ignored directory can be re-included, as in git, so a walk that
+    /// leaves ignored directories never asks about what is below them.
+    pub(crate) fn ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Some(parent) = path.parent().filter(|p| p.starts_with(&self.root)) else {
+            return false;
+        };
+
+        // Deeper directories take precedence, so they come last
+        let ancestors = parent
+            .ancestors()
+            .take_while(|d| d.starts_with(&self.root))
+            .collect::<Vec<_>>();
+        let dirs = ancestors
+            .into_iter()
+            .rev()
+            .map(|d| self.rules(d))
+            .collect::<Vec<_>>();
+
+        let mut ignored = false;
+        for rule in self.global.iter().chain(dirs.iter().flat_map(|d| d.iter())) {
+            if rule.matches(path, is_dir) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+
+    /// The patterns from the ignore files in `dir`, read once.
+    fn rules(&mut self, dir: &Path) -> Rc<Vec<Rule>> {
+        let git = self.git;
+        let rules = self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+            let files = IGNORE_FILES
+                .iter()
+                .filter(|name| git || **name != ".gitignore");
+            Rc::new(files.flat_map(|name| read(dir, &dir.join(name))).collect())
+        });
+        Rc::clone(rules)
+    }
+}
+
+/// The patterns in the ignore file at `path`, relative to `base`. A missing
+/// file has none, and lines that are not valid patterns are passed over, as
+/// git does.
+fn read(base: &Path, path: &Path) -> Vec<Rule> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    let mut rules = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // `\#` and `\!` start a pattern with a literal `#` or `!`
+        let line = line
+            .strip_prefix('\\')
+            .filter(|rest| rest.starts_with(['#', '!']))
+            .unwrap_or(line);
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        // A slash anywhere but at the end ties the pattern to `base`
+        let glob = match line.contains('/') {
+            true => Glob::anchored(line.strip_prefix('/').unwrap_or(line)),
+            false => Glob::new(line),
+        };
+        if let Ok(glob) = glob {
+            rules.push(Rule {
+                base: base.to_path_buf(),
+                glob,
+                negated,
+                dir_only,
+            });
+        }
+    }
+    rules
+}
+
+/// git's global excludes file: `core.excludesFile` from the user's or the
+/// repository's config, or `$XDG_CONFIG_HOME/git/ignore`.
+fn excludes_file(git_dir: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => Some(PathBuf::from(dir)),
+        _ => home.as_ref().map(|home| home.join(".config")),
+    };
+
+    // Later config files override earlier ones
+    let configs = [
+        config_home.as_ref().map(|dir| dir.join("git/config")),
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        Some(git_dir.join("config")),
+    ];
+    let configured = configs
+        .iter()
+        .flatten()
+        .filter_map(|config| core_excludes_file(config))
+        .next_back();
+
+    match configured {
+        Some(file) => match file.strip_prefix("~/") {
+            Some(rest) => home.map(|home| home.join(rest)),
+            None => Some(PathBuf::from(file)),
+        },
+        None => config_home.map(|dir| dir.join("git/ignore")),
+    }
+}
+
+/// The `excludesFile` setting of the `[core]` section in a git config file.
+fn core_excludes_file(config: &Path) -> Option<String> {
+    let contents = fs::read_to_string(config).ok()?;
+
+    let mut core = false;
+    let mut value = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(section) = line.strip_prefix('[') {
+            let name = section.split([']', ' ', '"']).next().unwrap_or_default();
+            core = name.eq_ignore_ascii_case("core");
+        } else if core
+            && let Some((key, rest)) = line.split_once('=')
+            && key.trim().eq_ignore_ascii_case("excludesfile")
+        {
+            value = Some(rest.trim().trim_matches('"').to_string());
+        }
+    }
+    value
+}
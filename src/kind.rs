@@ -0,0 +1,77 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+
+/// What kind of filesystem entry a candidate is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    /// A block or character device
+    Device,
+}
+
+impl FileKind {
+    /// The kind of entry `file_type` describes.
+    pub fn of(file_type: fs::FileType) -> FileKind {
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else if file_type.is_block_device() || file_type.is_char_device() {
+            FileKind::Device
+        } else {
+            FileKind::File
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Directory => "directory",
+            FileKind::Symlink => "symlink",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::Device => "device",
+        }
+    }
+
+    /// Suffix marking the kind in listings, like `ls -F`.
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            FileKind::File => "",
+            FileKind::Directory => "/",
+            FileKind::Symlink => "@",
+            FileKind::Fifo => "|",
+            FileKind::Socket => "=",
+            FileKind::Device => "#",
+        }
+    }
+
+    /// FIFOs, sockets and devices.
+    pub fn is_special(&self) -> bool {
+        matches!(self, FileKind::Fifo | FileKind::Socket | FileKind::Device)
+    }
+}
+
+/// How symlinks among the candidates are treated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Leave symlinks out
+    Skip,
+    /// Rename the link itself, even when it dangles (default)
+    #[default]
+    Link,
+    /// Rename the file the link points to, in that file's directory
+    Target,
+}
@@ -1,3 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+mod conflict;
+mod error;
+mod glob;
+mod identity;
+mod ignore;
+mod kind;
+mod links;
+mod plan;
+mod preflight;
+mod renamer;
+#[cfg(test)]
+mod testing;
+mod transfer;
+mod trash;
+
+pub use conflict::{OnConflict, Resolution};
+pub use error::{Candidate, Error, Result};
+pub use glob::Glob;
+pub use identity::SameData;
+pub use kind::{FileKind, SymlinkPolicy};
+pub use links::Relinked;
+pub use plan::{PlannedRename, RenamePlan};
+pub use renamer::{Explanation, Rename, Renamed, Renamer, Resolved};
+pub use transfer::move_path;
+pub use trash::{trash, untrash};
+
 /**
  * Match if two filenames differ only by extension.
  *
@@ -135,9 +163,68 @@ pub fn matches_expansion(old: &str, new: &str) -> bool {
     i1 == j1 && i1 > 0
 }
 
+/**
+ * The rule under which an existing filename matched the requested new name.
+ *
+ * Variants are ordered from most to least specific, so sorting candidates by
+ * rule puts the most likely intent first.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchRule {
+    /// Same base name, only the extension differs (data.txt -> data.csv)
+    ExtensionChange,
+    /// The new name adds characters to the old one (data.json -> data_v2.json)
+    Expansion,
+    /// The new name removes characters from the old one (data_v2.json -> data.json)
+    Reduction,
+}
+
+impl MatchRule {
+    /// Short human readable explanation of the rule.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            MatchRule::ExtensionChange => "extension change",
+            MatchRule::Expansion => "expansion",
+            MatchRule::Reduction => "reduction",
+        }
+    }
+}
+
+impl std::fmt::Display for MatchRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.describe())
+    }
+}
+
+/**
+ * Determine which rule, if any, makes `old` a candidate for renaming to `new`.
+ *
+ * Both directions are considered, mirroring how `rn` scans a directory:
+ * extension changes are checked first, then expansion (`old` grows into
+ * `new`), then reduction (`new` is a shortened form of `old`).
+ *
+ * Examples:
+ * old: data.txt,        new: data.csv       -> Some(ExtensionChange)
+ * old: report.csv,      new: report_v2.csv  -> Some(Expansion)
+ * old: report_v2.csv,   new: report.csv     -> Some(Reduction)
+ * old: data.json,       new: metadata.json  -> None
+ */
+pub fn match_rule(old: &str, new: &str) -> Option<MatchRule> {
+    if matches_extension_change(old, new) || matches_extension_change(new, old) {
+        Some(MatchRule::ExtensionChange)
+    } else if matches_expansion(old, new) {
+        Some(MatchRule::Expansion)
+    } else if matches_expansion(new, old) {
+        Some(MatchRule::Reduction)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{matches_expansion, matches_extension_change};
+    use super::{MatchRule, match_rule, matches_expansion, matches_extension_change};
 
     #[test]
     fn test_expansion_with_underscore() {
@@ -595,4 +682,45 @@ mod tests {
         assert!(matches_expansion("README", "README.md"));
         assert!(matches_expansion("LICENSE", "LICENSE.txt"));
     }
+
+    // ============================================================================
+    // MATCH RULE TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_match_rule_classification() {
+        assert_eq!(
+            match_rule("data.txt", "data.csv"),
+            Some(MatchRule::ExtensionChange)
+        );
+        assert_eq!(
+            match_rule("report.csv", "report_v2.csv"),
+            Some(MatchRule::Expansion)
+        );
+        assert_eq!(
+            match_rule("report_v2.csv", "report.csv"),
+            Some(MatchRule::Reduction)
+        );
+        assert_eq!(
+            match_rule("README", "README.md"),
+            Some(MatchRule::Expansion)
+        );
+        assert_eq!(
+            match_rule("README.md", "README"),
+            Some(MatchRule::Reduction)
+        );
+        assert_eq!(match_rule("data.json", "metadata.json"), None);
+        assert_eq!(match_rule("file.txt", "file.txt"), None);
+    }
+
+    #[test]
+    fn test_match_rule_prefers_extension_change() {
+        // config.yml -> config.yaml matches both patterns; extension change wins
+        assert_eq!(
+            match_rule("config.yml", "config.yaml"),
+            Some(MatchRule::ExtensionChange)
+        );
+        assert!(MatchRule::ExtensionChange < MatchRule::Expansion);
+        assert!(MatchRule::Expansion < MatchRule::Reduction);
+    }
 }
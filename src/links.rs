@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::renamer::walk;
+use crate::{Error, Result, transfer};
+
+/// A symlink rewritten to follow a rename of the file it points to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relinked {
+    /// The symlink itself
+    pub link: PathBuf,
+    /// What the link said before, relative or absolute as it was written
+    pub old: PathBuf,
+    /// What the link says now, in the same form
+    pub new: PathBuf,
+}
+
+impl Relinked {
+    /// Point the link at `new`, if it still says `old`.
+    pub fn apply(&self) -> Result<()> {
+        retarget(&self.link, &self.old, &self.new)
+    }
+
+    /// Point the link back at `old`, if it still says `new`.
+    pub fn revert(&self) -> Result<()> {
+        retarget(&self.link, &self.new, &self.old)
+    }
+}
+
+/// The symlinks under `root` (only directly in it unless `recursive`) that
+/// point at `file` by name, each with what it says.
+///
+/// Links that reach `file` through another symlink are left alone; they
+/// follow along once the link they go through is updated.
+pub(crate) fn find(root: &Path, recursive: bool, file: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+        return Ok(Vec::new());
+    };
+
+    // Recorded links must stay valid from any working directory
+    let root = root
+        .canonicalize()
+        .map_err(|e| Error::io(format!("Failed to read '{}'", root.display()), e))?;
+    let mut paths = Vec::new();
+    walk(
+        &root,
+        (!recursive).then_some(1),
+        &mut |_, _| Some(true),
+        &mut paths,
+    )?;
+
+    let mut links = Vec::new();
+    for path in paths {
+        if path == file {
+            continue;
+        }
+        let Ok(contents) = fs::read_link(&path) else {
+            continue;
+        };
+        let Some(link_dir) = path.parent() else {
+            continue;
+        };
+
+        // The link's own directory is where relative contents start from
+        let pointee = link_dir.join(&contents);
+        let points_at_file = pointee.file_name() == Some(name)
+            && pointee
+                .parent()
+                .and_then(|parent| parent.canonicalize().ok())
+                .is_some_and(|parent| parent == dir);
+        if points_at_file {
+            links.push((path, contents));
+        }
+    }
+    Ok(links)
+}
+
+/// Atomically replace `link`, which must say `from`, with one saying `to`.
+fn retarget(link: &Path, from: &Path, to: &Path) -> Result<()> {
+    let fail = |e| Error::io(format!("Failed to update symlink '{}'", link.display()), e);
+
+    match fs::read_link(link) {
+        Ok(contents) if contents == from => {}
+        Ok(_) => {
+            return Err(Error::Stale(format!(
+                "'{}' no longer points at '{}'.",
+                link.display(),
+                from.display()
+            )));
+        }
+        Err(e) => return Err(fail(e)),
+    }
+
+    let tmp = transfer::temp_path(link);
+    std::os::unix::fs::symlink(to, &tmp).map_err(fail)?;
+    fs::rename(&tmp, link).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        fail(e)
+    })
+}
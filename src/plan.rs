@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::{Error, OnConflict, Rename, Result, preflight};
+
+/// Version written to new plans; plans with any other version are refused.
+const PLAN_VERSION: u32 = 1;
+
+/// Renames worked out ahead of time, to be reviewed and applied later.
+///
+/// Each rename remembers the inode and modification time its source had when
+/// planned, so [`RenamePlan::check`] can refuse a plan the filesystem has
+/// moved on from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamePlan {
+    /// Format version of the plan file
+    pub version: u32,
+    pub renames: Vec<PlannedRename>,
+}
+
+/// One rename in a [`RenamePlan`], with the state of its source when planned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedRename {
+    #[serde(flatten)]
+    pub rename: Rename,
+    /// Inode of the source
+    pub inode: u64,
+    /// Modification time of the source, seconds since the epoch
+    pub mtime: i64,
+    /// Nanosecond part of the modification time
+    pub mtime_nsec: i64,
+}
+
+impl PlannedRename {
+    /// Record `rename` along with the current state of its source.
+    pub fn new(rename: Rename) -> Result<PlannedRename> {
+        let metadata = fs::symlink_metadata(&rename.source)
+            .map_err(|e| Error::io(format!("Failed to stat '{}'", rename.source.display()), e))?;
+
+        Ok(PlannedRename {
+            inode: metadata.ino(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            rename,
+        })
+    }
+
+    /// Check that the source is unchanged and the rename can still happen
+    /// exactly as planned.
+    pub fn check(&self) -> Result<()> {
+        let Rename {
+            source,
+            target,
+            conflict,
+            backup,
+            ..
+        } = &self.rename;
+
+        match fs::symlink_metadata(source) {
+            Ok(m)
+                if m.ino() == self.inode
+                    && (m.mtime(), m.mtime_nsec()) == (self.mtime, self.mtime_nsec) => {}
+            Ok(_) => {
+                return Err(Error::Stale(format!(
+                    "'{}' has changed since the plan was made.",
+                    source.display()
+                )));
+            }
+            Err(e) => {
+                return Err(Error::Stale(format!(
+                    "'{}' is no longer there: {}",
+                    source.display(),
+                    e
+                )));
+            }
+        }
+
+        // Only the strategies that replace a target may find one there
+        let replacing = matches!(
+            conflict,
+            Some(OnConflict::Overwrite | OnConflict::Backup | OnConflict::Numbered)
+        );
+        if !replacing && fs::symlink_metadata(target).is_ok() {
+            return Err(Error::TargetExists(target.clone()));
+        }
+        if let Some(backup) = backup
+            && fs::symlink_metadata(backup).is_ok()
+        {
+            return Err(Error::TargetExists(backup.clone()));
+        }
+
+        preflight::check(source, target, replacing)
+    }
+}
+
+impl RenamePlan {
+    pub fn new(renames: Vec<PlannedRename>) -> RenamePlan {
+        RenamePlan {
+            version: PLAN_VERSION,
+            renames,
+        }
+    }
+
+    /// Check every rename in the plan before any of them is applied.
+    pub fn check(&self) -> Result<()> {
+        self.renames.iter().try_for_each(PlannedRename::check)
+    }
+
+    /// Serialize the plan as pretty-printed JSON, for review.
+    ///
+    /// Fails only for paths that are not valid UTF-8.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| {
+            Error::io(
+                "Failed to write plan",
+                io::Error::new(io::ErrorKind::InvalidData, e),
+            )
+        })
+    }
+
+    /// Read a plan written by [`RenamePlan::to_json`].
+    pub fn load(path: &Path) -> Result<RenamePlan> {
+        let fail = |e: io::Error| Error::io(format!("Failed to read plan '{}'", path.display()), e);
+
+        let contents = fs::read_to_string(path).map_err(fail)?;
+        let plan: RenamePlan = serde_json::from_str(&contents)
+            .map_err(|e| fail(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        if plan.version != PLAN_VERSION {
+            return Err(fail(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported plan version {}", plan.version),
+            )));
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlannedRename, RenamePlan};
+    use crate::testing::scratch;
+    use crate::{Error, MatchRule, Rename};
+    use std::fs;
+    use std::path::Path;
+
+    fn planned(dir: &Path) -> PlannedRename {
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+        PlannedRename::new(Rename {
+            source: dir.join("notes.txt"),
+            target: dir.join("notes.md"),
+            requested: dir.join("notes.md"),
+            rule: MatchRule::ExtensionChange,
+            conflict: None,
+            backup: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_round_trips_through_json() {
+        let dir = scratch("json");
+        let plan = RenamePlan::new(vec![planned(&dir)]);
+        let path = dir.join("plan.json");
+        fs::write(&path, plan.to_json().unwrap()).unwrap();
+
+        assert_eq!(RenamePlan::load(&path).unwrap(), plan);
+        assert!(plan.check().is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_refuses_changed_source_and_taken_target() {
+        let dir = scratch("stale");
+        let plan = RenamePlan::new(vec![planned(&dir)]);
+
+        fs::write(dir.join("notes.md"), "taken").unwrap();
+        assert!(matches!(plan.check(), Err(Error::TargetExists(_))));
+        fs::remove_file(dir.join("notes.md")).unwrap();
+
+        // A replaced file has a new inode even if the name is the same
+        fs::write(dir.join("notes.new"), "replaced").unwrap();
+        fs::rename(dir.join("notes.new"), dir.join("notes.txt")).unwrap();
+        assert!(matches!(plan.check(), Err(Error::Stale(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+use crate::{Error, Result};
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Check that `source` can be renamed to `target` before touching anything,
+/// so a refusal names the exact reason instead of a bare OS error.
+///
+/// `replacing` is set when an existing target will be moved or overwritten,
+/// which requires the same rights over the target as over the source.
+pub fn check(source: &Path, target: &Path, replacing: bool) -> Result<()> {
+    let source_dir = parent(source);
+    let target_dir = parent(target);
+
+    check_name_length(target, target_dir)?;
+    check_subtree(source, target)?;
+
+    for dir in [source_dir, target_dir] {
+        check_writable_fs(dir)?;
+        check_dir_access(dir)?;
+        check_dir_attributes(dir)?;
+    }
+
+    check_sticky(source, source_dir)?;
+    check_file_attributes(source)?;
+    if replacing {
+        check_sticky(target, target_dir)?;
+        check_file_attributes(target)?;
+        check_same_kind(source, target)?;
+    }
+
+    Ok(())
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::InvalidName(path.display().to_string()))
+}
+
+/// The new name must fit the filesystem's NAME_MAX and PATH_MAX.
+fn check_name_length(target: &Path, dir: &Path) -> Result<()> {
+    let c_dir = c_path(dir)?;
+    let name_len = target.file_name().map_or(0, |n| n.as_bytes().len());
+    let path_len = target.as_os_str().as_bytes().len();
+
+    // SAFETY: pathconf only reads the NUL-terminated path
+    let (name_max, path_max) = unsafe {
+        (
+            libc::pathconf(c_dir.as_ptr(), libc::_PC_NAME_MAX),
+            libc::pathconf(c_dir.as_ptr(), libc::_PC_PATH_MAX),
+        )
+    };
+
+    // pathconf returns -1 when there is no limit
+    if name_max > 0 && name_len as libc::c_long > name_max {
+        return Err(Error::NotPermitted(format!(
+            "the new name is {} bytes long, but '{}' allows at most {}.",
+            name_len,
+            dir.display(),
+            name_max
+        )));
+    }
+    if path_max > 0 && path_len as libc::c_long >= path_max {
+        return Err(Error::NotPermitted(format!(
+            "the new path is {} bytes long, but the system allows at most {}.",
+            path_len,
+            path_max - 1
+        )));
+    }
+    Ok(())
+}
+
+/// A directory cannot be moved into itself or below.
+fn check_subtree(source: &Path, target: &Path) -> Result<()> {
+    if target != source && target.starts_with(source) && source.is_dir() {
+        return Err(Error::NotPermitted(format!(
+            "'{}' cannot be moved into itself.",
+            source.display()
+        )));
+    }
+    Ok(())
+}
+
+/// A directory can only replace a directory, and a file only a file.
+fn check_same_kind(source: &Path, target: &Path) -> Result<()> {
+    let (Ok(source_meta), Ok(target_meta)) = (fs::metadata(source), fs::metadata(target)) else {
+        return Ok(());
+    };
+    match (source_meta.is_dir(), target_meta.is_dir()) {
+        (true, false) => Err(Error::NotPermitted(format!(
+            "'{}' is a directory and cannot replace the file '{}'.",
+            source.display(),
+            target.display()
+        ))),
+        (false, true) => Err(Error::NotPermitted(format!(
+            "'{}' is a file and cannot replace the directory '{}'.",
+            source.display(),
+            target.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// An overwritten directory must be empty, as the kernel requires when it
+/// replaces one. Checked only when nothing moves the target away first.
+pub fn check_overwritable(target: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(target);
+    if metadata.is_ok_and(|m| m.is_dir())
+        && fs::read_dir(target).is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(Error::NotPermitted(format!(
+            "'{}' is a directory that is not empty and cannot be overwritten.",
+            target.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse early on read-only mounts.
+fn check_writable_fs(dir: &Path) -> Result<()> {
+    let c_dir = c_path(dir)?;
+    // SAFETY: statvfs writes into the zeroed struct we own
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_dir.as_ptr(), &mut stat) != 0 {
+            return Ok(());
+        }
+        stat
+    };
+
+    if stat.f_flag & libc::ST_RDONLY != 0 {
+        return Err(Error::NotPermitted(format!(
+            "'{}' is on a read-only filesystem.",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Renaming needs write and search permission on the directory.
+fn check_dir_access(dir: &Path) -> Result<()> {
+    let c_dir = c_path(dir)?;
+    // SAFETY: access only reads the NUL-terminated path
+    let (writable, searchable) = unsafe {
+        (
+            libc::access(c_dir.as_ptr(), libc::W_OK) == 0,
+            libc::access(c_dir.as_ptr(), libc::X_OK) == 0,
+        )
+    };
+
+    if !writable {
+        return Err(Error::NotPermitted(format!(
+            "no write permission on directory '{}'.",
+            dir.display()
+        )));
+    }
+    if !searchable {
+        return Err(Error::NotPermitted(format!(
+            "no search (execute) permission on directory '{}'.",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// In a sticky directory (like /tmp) only the owner of the file or of the
+/// directory, or root, may rename a file.
+fn check_sticky(file: &Path, dir: &Path) -> Result<()> {
+    const S_ISVTX: u32 = 0o1000;
+
+    let (Ok(dir_meta), Ok(file_meta)) = (fs::metadata(dir), fs::symlink_metadata(file)) else {
+        return Ok(());
+    };
+    if dir_meta.mode() & S_ISVTX == 0 {
+        return Ok(());
+    }
+
+    // SAFETY: geteuid cannot fail
+    let euid = unsafe { libc::geteuid() };
+    if euid != 0 && euid != file_meta.uid() && euid != dir_meta.uid() {
+        return Err(Error::NotPermitted(format!(
+            "'{}' has the sticky bit set and '{}' belongs to another user (uid {}).",
+            dir.display(),
+            file.file_name().unwrap_or_default().to_string_lossy(),
+            file_meta.uid()
+        )));
+    }
+    Ok(())
+}
+
+/// Immutable or append-only files cannot be renamed, even by root.
+fn check_file_attributes(file: &Path) -> Result<()> {
+    match attributes(file) {
+        Some(Attribute::Immutable) => Err(Error::NotPermitted(format!(
+            "'{}' is immutable (see chattr/chflags).",
+            file.display()
+        ))),
+        Some(Attribute::AppendOnly) => Err(Error::NotPermitted(format!(
+            "'{}' is append-only (see chattr/chflags).",
+            file.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Entries cannot be added to an immutable directory or removed from an
+/// append-only one, so both rule out a rename.
+fn check_dir_attributes(dir: &Path) -> Result<()> {
+    match attributes(dir) {
+        Some(Attribute::Immutable) => Err(Error::NotPermitted(format!(
+            "directory '{}' is immutable (see chattr/chflags).",
+            dir.display()
+        ))),
+        Some(Attribute::AppendOnly) => Err(Error::NotPermitted(format!(
+            "directory '{}' is append-only (see chattr/chflags).",
+            dir.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+enum Attribute {
+    Immutable,
+    AppendOnly,
+}
+
+#[cfg(target_os = "linux")]
+fn attributes(path: &Path) -> Option<Attribute> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    const FS_IMMUTABLE_FL: libc::c_long = 0x10;
+    const FS_APPEND_FL: libc::c_long = 0x20;
+
+    // Reading the flags takes an open file, and opening a FIFO or a device
+    // can have side effects, so only regular files and directories are
+    // checked. Symlinks carry no attributes of their own; unreadable files
+    // are left for the rename itself to report
+    let file_type = fs::symlink_metadata(path).ok()?.file_type();
+    if !file_type.is_file() && !file_type.is_dir() {
+        return None;
+    }
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+
+    let mut flags: libc::c_long = 0;
+    // SAFETY: FS_IOC_GETFLAGS writes a long into `flags`
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        return None;
+    }
+
+    if flags & FS_IMMUTABLE_FL != 0 {
+        Some(Attribute::Immutable)
+    } else if flags & FS_APPEND_FL != 0 {
+        Some(Attribute::AppendOnly)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn attributes(path: &Path) -> Option<Attribute> {
+    use std::os::macos::fs::MetadataExt;
+
+    const UF_IMMUTABLE: u32 = 0x0000_0002;
+    const UF_APPEND: u32 = 0x0000_0004;
+    const SF_IMMUTABLE: u32 = 0x0002_0000;
+    const SF_APPEND: u32 = 0x0004_0000;
+
+    let flags = fs::symlink_metadata(path).ok()?.st_flags();
+    if flags & (UF_IMMUTABLE | SF_IMMUTABLE) != 0 {
+        Some(Attribute::Immutable)
+    } else if flags & (UF_APPEND | SF_APPEND) != 0 {
+        Some(Attribute::AppendOnly)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn attributes(_path: &Path) -> Option<Attribute> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::Error;
+    use crate::testing::scratch;
+    use std::fs;
+
+    #[test]
+    fn test_preflight_name_length() {
+        let dir = scratch("length");
+        let source = dir.join("notes.txt");
+        fs::write(&source, "").unwrap();
+
+        let long = dir.join(format!("notes_{}.txt", "x".repeat(300)));
+        match check(&source, &long, false) {
+            Err(Error::NotPermitted(reason)) => {
+                assert!(
+                    reason.starts_with("the new name is 310 bytes long"),
+                    "{}",
+                    reason
+                )
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(check(&source, &dir.join("notes_v2.txt"), false).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preflight_subtree_and_kind() {
+        let dir = scratch("subtree");
+        let source = dir.join("src");
+        fs::create_dir_all(source.join("deep")).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+
+        for target in [source.join("src_v2"), source.join("deep/src")] {
+            match check(&source, &target, false) {
+                Err(Error::NotPermitted(reason)) => assert!(reason.contains("into itself")),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        // A sibling that merely shares the prefix is fine
+        assert!(check(&source, &dir.join("src_v2"), false).is_ok());
+
+        // Only a directory replaces a directory
+        assert!(matches!(
+            check(&source, &dir.join("file"), true),
+            Err(Error::NotPermitted(_))
+        ));
+        assert!(matches!(
+            check(&dir.join("file"), &source, true),
+            Err(Error::NotPermitted(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preflight_special_files() {
+        let dir = scratch("special");
+        let fifo = std::ffi::CString::new(dir.join("pipe.in").to_str().unwrap()).unwrap();
+        // SAFETY: mkfifo only reads the NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        // The FIFO is checked without being opened
+        assert!(super::attributes(&dir.join("pipe.in")).is_none());
+        assert!(check(&dir.join("pipe.in"), &dir.join("pipe.out"), false).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1645 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::conflict::{self, OnConflict, Resolution};
+use crate::ignore::Ignores;
+use crate::{
+    Candidate, Error, FileKind, Glob, MatchRule, PlannedRename, Relinked, RenamePlan, Result,
+    SameData, SymlinkPolicy, preflight, transfer, trash,
+};
+use crate::{identity, links};
+
+/// Picks one of several ranked candidates by index, or `None` to abort.
+type Chooser = dyn Fn(&str, &[Candidate]) -> Result<Option<usize>>;
+/// Decides whether a directory entry may be considered at all.
+type Filter = dyn Fn(&Path) -> bool;
+
+/// An entry that may be renamed.
+struct Entry {
+    /// Where it was found, which gives the name it is matched by
+    path: PathBuf,
+    /// What would be renamed: the entry itself, or the file a symlink
+    /// points to
+    source: PathBuf,
+    /// The kind of `source`
+    kind: FileKind,
+}
+
+type Listing = Vec<Entry>;
+
+/// Where candidates for a new name come from.
+enum Search {
+    /// The files in the searched directory
+    Directory,
+    /// The files below the searched directory, down to an optional depth
+    Recursive(Option<usize>),
+    /// Explicitly listed files below the searched directory
+    Listed(Vec<PathBuf>),
+}
+
+/// Infers which existing file a new name refers to, and renames it.
+///
+/// Configured with builder methods; the defaults match `rn` without flags:
+/// every [`MatchRule`] enabled, regular files only, and refusing to touch an
+/// existing target.
+///
+/// ```no_run
+/// use snipren::{OnConflict, Renamer};
+///
+/// let renamer = Renamer::new().on_conflict(OnConflict::Backup);
+/// if let Some(renamed) = renamer.rename("report_v2.csv")? {
+///     println!("{} -> {}", renamed.source.display(), renamed.target.display());
+/// }
+/// # Ok::<(), snipren::Error>(())
+/// ```
+pub struct Renamer {
+    rules: Vec<MatchRule>,
+    filters: Vec<Box<Filter>>,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    chooser: Option<Box<Chooser>>,
+    on_conflict: OnConflict,
+    trash: bool,
+    verify: bool,
+    dirs: bool,
+    symlinks: SymlinkPolicy,
+    special: bool,
+    update_links: bool,
+    link_tree: Option<PathBuf>,
+    same_data: SameData,
+    compare_contents: bool,
+    hidden: bool,
+    ignore_files: bool,
+    search: Search,
+    /// Directory listings kept while a batch is inferred, so each directory
+    /// is read once however many names point into it
+    listings: RefCell<Option<HashMap<PathBuf, Rc<Listing>>>>,
+}
+
+/// A rename that has been inferred and checked, but not yet performed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rename {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    /// The path that was asked for; differs from `target` under `suffix`
+    pub requested: PathBuf,
+    pub rule: MatchRule,
+    /// The strategy in effect because the requested name was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<OnConflict>,
+    /// Where the existing target will be kept, for `backup` and `numbered`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+}
+
+/// The file a new name was resolved to, see [`Renamer::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub source: PathBuf,
+    pub rule: MatchRule,
+}
+
+/// Everything [`Renamer::infer`] considered for a new name.
+#[derive(Debug)]
+pub struct Explanation {
+    /// The directory that was searched
+    pub directory: PathBuf,
+    /// Whether the requested name is already taken
+    pub target_exists: bool,
+    /// Every matching file, most likely intent first
+    pub candidates: Vec<Candidate>,
+    /// What a rename would do: the rename, `None` for a skip, or why it
+    /// would fail
+    pub outcome: Result<Option<Rename>>,
+}
+
+/// A completed rename.
+#[derive(Debug, Clone)]
+pub struct Renamed {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub rule: MatchRule,
+    /// How an existing target was dealt with, if there was one
+    pub conflict: Option<Resolution>,
+    /// Symlinks rewritten to point at the new name
+    pub relinked: Vec<Relinked>,
+}
+
+impl Default for Renamer {
+    fn default() -> Self {
+        Renamer {
+            rules: vec![
+                MatchRule::ExtensionChange,
+                MatchRule::Expansion,
+                MatchRule::Reduction,
+            ],
+            filters: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            chooser: None,
+            on_conflict: OnConflict::default(),
+            trash: false,
+            verify: false,
+            dirs: false,
+            symlinks: SymlinkPolicy::default(),
+            special: false,
+            update_links: false,
+            link_tree: None,
+            same_data: SameData::default(),
+            compare_contents: false,
+            hidden: false,
+            ignore_files: true,
+            search: Search::Directory,
+            listings: RefCell::new(None),
+        }
+    }
+}
+
+impl Renamer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept candidates matching one of `rules`.
+    pub fn rules(mut self, rules: impl IntoIterator<Item = MatchRule>) -> Self {
+        self.rules = rules.into_iter().collect();
+        self
+    }
+
+    /// Only consider files for which `filter` returns true. Filters add up.
+    pub fn filter(mut self, filter: impl Fn(&Path) -> bool + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Only consider entries matching one of `globs`, if there are any. The
+    /// globs are matched against the path relative to the searched
+    /// directory, so the directories above it never match.
+    pub fn include(mut self, globs: impl IntoIterator<Item = Glob>) -> Self {
+        self.include.extend(globs);
+        self
+    }
+
+    /// Never consider entries matching any of `globs`, matched like those
+    /// of [`Renamer::include`].
+    pub fn exclude(mut self, globs: impl IntoIterator<Item = Glob>) -> Self {
+        self.exclude.extend(globs);
+        self
+    }
+
+    /// Resolve ambiguous matches with `chooser` instead of failing with
+    /// [`Error::Ambiguous`]. Returning `None` aborts the rename.
+    pub fn chooser(
+        mut self,
+        chooser: impl Fn(&str, &[Candidate]) -> Result<Option<usize>> + 'static,
+    ) -> Self {
+        self.chooser = Some(Box::new(chooser));
+        self
+    }
+
+    /// What to do when the target name is already taken.
+    pub fn on_conflict(mut self, strategy: OnConflict) -> Self {
+        self.on_conflict = strategy;
+        self
+    }
+
+    /// Move an overwritten target to the trash instead of deleting it.
+    pub fn trash(mut self, trash: bool) -> Self {
+        self.trash = trash;
+        self
+    }
+
+    /// Compare checksums, not just sizes, when a move has to copy across
+    /// filesystems.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Consider directories as well as files, under the same rules.
+    pub fn dirs(mut self, dirs: bool) -> Self {
+        self.dirs = dirs;
+        self
+    }
+
+    /// How to treat symlinks: leave them out, rename the link, or rename
+    /// the file it points to.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Consider FIFOs, sockets and devices as well.
+    pub fn special(mut self, special: bool) -> Self {
+        self.special = special;
+        self
+    }
+
+    /// Rewrite symlinks that point at a renamed file so they follow it.
+    /// Links are looked for in the file's directory, or below `link_tree`.
+    pub fn update_symlinks(mut self, update: bool) -> Self {
+        self.update_links = update;
+        self
+    }
+
+    /// Look for symlinks to update anywhere below `root` instead of only in
+    /// the renamed file's directory.
+    pub fn symlink_tree(mut self, root: Option<PathBuf>) -> Self {
+        self.link_tree = root;
+        self
+    }
+
+    /// Consider hidden files, and files in hidden directories, even when the
+    /// new name is not hidden itself.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Leave out what `.gitignore`, `.ignore` and git's other exclude files
+    /// ignore while looking through directories; on by default. Listed
+    /// candidates are taken as they are.
+    pub fn ignore_files(mut self, respect: bool) -> Self {
+        self.ignore_files = respect;
+        self
+    }
+
+    /// Which name to rename when every candidate holds the same data,
+    /// instead of reporting the match as ambiguous.
+    pub fn same_data(mut self, policy: SameData) -> Self {
+        self.same_data = policy;
+        self
+    }
+
+    /// Also treat byte-identical files as the same data, not only hardlinks
+    /// of one file.
+    pub fn compare_contents(mut self, compare: bool) -> Self {
+        self.compare_contents = compare;
+        self
+    }
+
+    /// Only consider `paths` as sources, instead of the files in the searched
+    /// directory. Listed files anywhere below the searched directory qualify,
+    /// and each is renamed within its own directory.
+    pub fn candidates_from(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.search = Search::Listed(paths.into_iter().collect());
+        self
+    }
+
+    /// Search the whole tree below the searched directory, or `max_depth`
+    /// levels of it, where 1 is the directory itself. Each match is renamed
+    /// within its own directory.
+    ///
+    /// Matches in different directories are ambiguous unless one ranks
+    /// strictly above the rest, by rule and then by how little the name
+    /// changes.
+    pub fn recursive(mut self, max_depth: Option<usize>) -> Self {
+        self.search = Search::Recursive(max_depth);
+        self
+    }
+
+    /// Infer the source for `new_name` and rename it.
+    ///
+    /// Returns `None` when the target exists and the strategy is `skip`.
+    pub fn rename(&self, new_name: &str) -> Result<Option<Renamed>> {
+        match self.infer(new_name)? {
+            Some(rename) => self.apply(&rename).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Work out what renaming to `new_name` would do, without touching
+    /// anything.
+    ///
+    /// The search happens in the directory part of `new_name`, or the current
+    /// directory. Returns `None` when the target exists and the strategy is
+    /// `skip`.
+    pub fn infer(&self, new_name: &str) -> Result<Option<Rename>> {
+        let (search_dir, new_filename) = locate(new_name)?;
+
+        // When every candidate is in the search directory, a taken name is
+        // reported before scanning, as the target does not depend on the source
+        let early = match (&self.search, self.symlinks) {
+            (Search::Directory, SymlinkPolicy::Skip | SymlinkPolicy::Link) => {
+                match self.conflict(&search_dir.join(new_filename))? {
+                    Some(resolved) => Some(resolved),
+                    None => return Ok(None),
+                }
+            }
+            _ => None,
+        };
+
+        let chosen = self.choose(&search_dir, new_filename)?;
+        let source = chosen.path;
+        let requested = source.with_file_name(new_filename);
+        let (target, conflict) = match early {
+            Some(resolved) => resolved,
+            None => match self.conflict(&requested)? {
+                Some(resolved) => resolved,
+                None => return Ok(None),
+            },
+        };
+
+        // Work out where a displaced target goes up front, so callers can say
+        let backup = match conflict {
+            Some(OnConflict::Backup) => Some(conflict::backup_path(&target)),
+            Some(OnConflict::Numbered) => Some(conflict::numbered_backup_path(&target)?),
+            _ => None,
+        };
+
+        // Explain exactly why a rename cannot happen before attempting it
+        let replacing = matches!(
+            conflict,
+            Some(OnConflict::Overwrite | OnConflict::Backup | OnConflict::Numbered)
+        );
+        preflight::check(&source, &target, replacing)?;
+        if conflict == Some(OnConflict::Overwrite) && !self.trash {
+            preflight::check_overwritable(&target)?;
+        }
+
+        Ok(Some(Rename {
+            source,
+            target,
+            requested,
+            rule: chosen.rule,
+            conflict,
+            backup,
+        }))
+    }
+
+    /// Check if `requested` already exists and decide how to deal with it:
+    /// the target to use and the strategy applied, or `None` to skip. A
+    /// dangling symlink exists too.
+    fn conflict(&self, requested: &Path) -> Result<Option<(PathBuf, Option<OnConflict>)>> {
+        if fs::symlink_metadata(requested).is_err() {
+            return Ok(Some((requested.to_path_buf(), None)));
+        }
+        match self.on_conflict {
+            OnConflict::Refuse => Err(Error::TargetExists(requested.to_path_buf())),
+            OnConflict::Skip => Ok(None),
+            OnConflict::Suffix => Ok(Some((
+                conflict::suffixed_path(requested)?,
+                Some(OnConflict::Suffix),
+            ))),
+            strategy => Ok(Some((requested.to_path_buf(), Some(strategy)))),
+        }
+    }
+
+    /// Find the file that renaming to `new_name` would start from, using
+    /// exactly the same matching as [`Renamer::infer`], but ignoring whether
+    /// the target exists or the rename is permitted.
+    pub fn resolve(&self, new_name: &str) -> Result<Resolved> {
+        let (search_dir, new_filename) = locate(new_name)?;
+        let chosen = self.choose(&search_dir, new_filename)?;
+        Ok(Resolved {
+            source: chosen.path,
+            rule: chosen.rule,
+        })
+    }
+
+    /// Show the reasoning behind [`Renamer::infer`] for `new_name`: the
+    /// directory searched, every candidate and the outcome.
+    pub fn explain(&self, new_name: &str) -> Result<Explanation> {
+        let (directory, new_filename) = locate(new_name)?;
+        Ok(Explanation {
+            target_exists: fs::symlink_metadata(directory.join(new_filename)).is_ok(),
+            candidates: self.candidates(&directory, new_filename)?,
+            outcome: self.infer(new_name),
+            directory,
+        })
+    }
+
+    /// The single candidate for `new_filename` in `dir`, asking the chooser
+    /// when there are several.
+    fn choose(&self, dir: &Path, new_filename: &str) -> Result<Candidate> {
+        let mut candidates = self.candidates(dir, new_filename)?;
+        match (candidates.len(), &self.chooser) {
+            (0, _) => Err(Error::NoMatch(new_filename.to_string())),
+            (1, _) => Ok(candidates.remove(0)),
+            _ if self.outranks(&candidates[0], &candidates[1], new_filename) => {
+                Ok(candidates.remove(0))
+            }
+            _ if let Some(index) = self.same_data_pick(&candidates) => {
+                Ok(candidates.swap_remove(index))
+            }
+            (_, Some(chooser)) => match chooser(new_filename, &candidates)? {
+                Some(index) if index < candidates.len() => Ok(candidates.swap_remove(index)),
+                _ => Err(Error::Aborted),
+            },
+            (_, None) => Err(Error::Ambiguous {
+                name: new_filename.to_string(),
+                candidates,
+            }),
+        }
+    }
+
+    /// The candidate the same-data policy settles on, when every candidate
+    /// holds the same data.
+    fn same_data_pick(&self, candidates: &[Candidate]) -> Option<usize> {
+        let group = candidates.first()?.group;
+        match candidates.iter().all(|c| c.group == group) {
+            true => self.same_data.pick(candidates),
+            false => None,
+        }
+    }
+
+    /// Whether a recursive search may settle on `best` over the runner-up:
+    /// only across directories, and only when `best` ranks strictly higher.
+    fn outranks(&self, best: &Candidate, next: &Candidate, new_filename: &str) -> bool {
+        matches!(self.search, Search::Recursive(_))
+            && best.path.parent() != next.path.parent()
+            && (best.rule, distance(best, new_filename)) < (next.rule, distance(next, new_filename))
+    }
+
+    /// Infer the renames for several new names at once, to apply later.
+    ///
+    /// Names skipped under the `skip` strategy are left out. Two names may not
+    /// claim the same source or the same target.
+    pub fn plan<'a>(&self, new_names: impl IntoIterator<Item = &'a str>) -> Result<RenamePlan> {
+        let renames = self
+            .infer_all(new_names)
+            .map_err(|(_, e)| e)?
+            .into_iter()
+            .flatten()
+            .map(PlannedRename::new)
+            .collect::<Result<_>>()?;
+        Ok(RenamePlan::new(renames))
+    }
+
+    /// Infer the renames for several new names, one entry per name, before
+    /// anything is renamed. `None` marks a name skipped under `skip`.
+    ///
+    /// The renames must be independent: two names may not claim the same
+    /// source or the same target, and no target may be another's source.
+    /// An error comes with the new name it is about.
+    pub fn infer_all<'a>(
+        &self,
+        new_names: impl IntoIterator<Item = &'a str>,
+    ) -> std::result::Result<Vec<Option<Rename>>, (&'a str, Error)> {
+        // Nothing is renamed until every name is inferred, so listings stay valid
+        *self.listings.borrow_mut() = Some(HashMap::new());
+        let renames = self.infer_each(new_names);
+        *self.listings.borrow_mut() = None;
+        renames
+    }
+
+    fn infer_each<'a>(
+        &self,
+        new_names: impl IntoIterator<Item = &'a str>,
+    ) -> std::result::Result<Vec<Option<Rename>>, (&'a str, Error)> {
+        let mut renames: Vec<Option<Rename>> = Vec::new();
+        for new_name in new_names {
+            let rename = self
+                .infer(new_name)
+                .and_then(|rename| match rename {
+                    Some(rename) => self.independent(&renames, rename).map(Some),
+                    None => Ok(None),
+                })
+                .map_err(|e| (new_name, e))?;
+            renames.push(rename);
+        }
+        Ok(renames)
+    }
+
+    /// `rename`, if it does not collide with any of the `renames` before it.
+    fn independent(&self, renames: &[Option<Rename>], rename: Rename) -> Result<Rename> {
+        for other in renames.iter().flatten() {
+            if other.source == rename.source {
+                return Err(Error::NotPermitted(format!(
+                    "'{}' would become both '{}' and '{}'.",
+                    file_name(&rename.source),
+                    file_name(&other.target),
+                    file_name(&rename.target)
+                )));
+            }
+            if other.target == rename.target {
+                return Err(Error::TargetExists(rename.target));
+            }
+            // Renaming a directory would move the other rename's paths
+            let inside = |path: &Path, dir: &Path| path != dir && path.starts_with(dir);
+            for (outer, inner) in [(other, &rename), (&rename, other)] {
+                if inside(&inner.source, &outer.source) || inside(&inner.target, &outer.source) {
+                    return Err(Error::NotPermitted(format!(
+                        "'{}' is inside '{}', which is renamed in the same batch.",
+                        inner.source.display(),
+                        outer.source.display()
+                    )));
+                }
+            }
+            if other.target == rename.source || other.source == rename.target {
+                let (first, second) = match other.target == rename.source {
+                    true => (other, &rename),
+                    false => (&rename, other),
+                };
+                return Err(Error::NotPermitted(format!(
+                    "'{}' is both the new name of '{}' and the file renamed to '{}'.",
+                    file_name(&first.target),
+                    file_name(&first.source),
+                    file_name(&second.target)
+                )));
+            }
+        }
+        Ok(rename)
+    }
+
+    /// Files in `dir`, or the listed files below it, that could be renamed to
+    /// `new_filename`, most likely intent first: by rule, then by how little
+    /// the name changes.
+    pub fn candidates(&self, dir: &Path, new_filename: &str) -> Result<Vec<Candidate>> {
+        let mut candidates = Vec::new();
+        for Entry { path, source, kind } in self.list(dir)?.iter() {
+            let filename = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| Error::InvalidName(path.display().to_string()))?;
+
+            // Skip the target name itself if it exists
+            if filename == new_filename {
+                continue;
+            }
+
+            // Hidden files only stand in for a hidden new name
+            let name = path.strip_prefix(dir).unwrap_or(path);
+            let hidden = name
+                .components()
+                .any(|c| c.as_os_str().as_encoded_bytes().starts_with(b"."));
+            if hidden && !self.hidden && !new_filename.starts_with('.') {
+                continue;
+            }
+
+            // Several links may lead to the same file
+            if candidates.iter().any(|c: &Candidate| c.path == *source) {
+                continue;
+            }
+
+            if let Some(rule) = crate::match_rule(filename, new_filename)
+                && self.rules.contains(&rule)
+            {
+                candidates.push(Candidate {
+                    name: name.display().to_string(),
+                    rule,
+                    path: source.clone(),
+                    kind: *kind,
+                    group: 0,
+                });
+            }
+        }
+
+        // Between otherwise equal matches, shallower files come first
+        let rank = |c: &Candidate| {
+            let depth = c.path.components().count();
+            (c.rule, distance(c, new_filename), depth)
+        };
+        candidates.sort_by(|a, b| (rank(a), &a.name).cmp(&(rank(b), &b.name)));
+        identity::group(&mut candidates, self.compare_contents)?;
+        Ok(candidates)
+    }
+
+    /// Paths of the entries that may be renamed from `dir`, with their kind:
+    /// its entries, those of its subdirectories when recursive, or the listed
+    /// candidates below it, that are not ignored and pass the filters.
+    fn list(&self, dir: &Path) -> Result<Rc<Listing>> {
+        if let Some(listing) = self.listings.borrow().as_ref().and_then(|l| l.get(dir)) {
+            return Ok(Rc::clone(listing));
+        }
+
+        // Ignored and hidden directories are left before the walk gets into
+        // them, which matters with `target/` or `.git/` below `dir`
+        let listed = matches!(self.search, Search::Listed(_));
+        let mut ignores = (self.ignore_files && !listed).then(|| Ignores::new(dir));
+        let mut visit = |path: &Path, is_dir: bool| {
+            if ignores.as_mut().is_some_and(|i| i.ignored(path, is_dir)) {
+                return None;
+            }
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.as_encoded_bytes().starts_with(b"."));
+            Some(self.hidden || !hidden)
+        };
+
+        let mut paths = Vec::new();
+        match &self.search {
+            Search::Directory => walk(dir, Some(1), &mut visit, &mut paths)?,
+            Search::Recursive(max_depth) => walk(dir, *max_depth, &mut visit, &mut paths)?,
+            Search::Listed(listed) => paths.extend(
+                listed
+                    .iter()
+                    .filter_map(|path| absolute(path).filter(|path| path.starts_with(dir))),
+            ),
+        }
+        if !self.include.is_empty() || !self.exclude.is_empty() {
+            paths.retain(|path| {
+                let relative = path.strip_prefix(dir).unwrap_or(path);
+                (self.include.is_empty() || self.include.iter().any(|g| g.matches(relative)))
+                    && !self.exclude.iter().any(|g| g.matches(relative))
+            });
+        }
+
+        let paths: Listing = paths
+            .into_iter()
+            .filter_map(|path| self.entry(path))
+            .filter(|entry| self.filters.iter().all(|filter| filter(&entry.path)))
+            .collect();
+
+        let paths = Rc::new(paths);
+        if let Some(listings) = self.listings.borrow_mut().as_mut() {
+            listings.insert(dir.to_path_buf(), Rc::clone(&paths));
+        }
+        Ok(paths)
+    }
+
+    /// What renaming `path` would rename, if it is a kind of entry wanted.
+    fn entry(&self, path: PathBuf) -> Option<Entry> {
+        let mut kind = FileKind::of(fs::symlink_metadata(&path).ok()?.file_type());
+        let mut source = path.clone();
+        if kind == FileKind::Symlink {
+            // A link is only wanted when what it leads to would be
+            let pointee = fs::metadata(&path)
+                .ok()
+                .map(|m| FileKind::of(m.file_type()));
+            match (self.symlinks, pointee) {
+                (SymlinkPolicy::Skip, _) | (SymlinkPolicy::Target, None) => return None,
+                (SymlinkPolicy::Link, Some(pointee)) if !self.wanted(pointee) => return None,
+                (SymlinkPolicy::Link, _) => {}
+                (SymlinkPolicy::Target, Some(pointee)) => {
+                    source = path.canonicalize().ok()?;
+                    kind = pointee;
+                }
+            }
+        }
+        self.wanted(kind).then_some(Entry { path, source, kind })
+    }
+
+    /// Whether entries of `kind` are candidates at all: files and the links
+    /// that got this far always, directories and special files only when
+    /// asked for.
+    fn wanted(&self, kind: FileKind) -> bool {
+        match kind {
+            FileKind::File | FileKind::Symlink => true,
+            FileKind::Directory => self.dirs,
+            _ => self.special,
+        }
+    }
+
+    /// Perform a rename produced by [`Renamer::infer`].
+    ///
+    /// A displaced target is backed up or trashed first and put back if the
+    /// rename itself fails, so a failed rename changes nothing.
+    pub fn apply(&self, rename: &Rename) -> Result<Renamed> {
+        let Rename {
+            source,
+            target,
+            conflict,
+            backup,
+            ..
+        } = rename;
+
+        // Links are found while they still resolve to the source
+        let links = match (self.update_links, &self.link_tree) {
+            (false, _) => Vec::new(),
+            (true, Some(root)) => links::find(root, true, source)?,
+            (true, None) => links::find(source.parent().unwrap_or(Path::new(".")), false, source)?,
+        };
+
+        // Move a displaced target out of the way first
+        if let Some(backup) = backup {
+            fs::rename(target, backup)
+                .map_err(|e| Error::io(format!("Failed to back up '{}'", file_name(target)), e))?;
+        }
+        let trashed = if *conflict == Some(OnConflict::Overwrite) && self.trash {
+            Some(trash::trash(target)?)
+        } else {
+            None
+        };
+
+        // Perform the rename, copying across filesystems if need be
+        if let Err(e) = transfer::move_path(source, target, self.verify) {
+            // Put the displaced target back so a failed rename changes nothing
+            if let Some(backup) = backup {
+                let _ = fs::rename(backup, target);
+            }
+            if let Some(trashed) = &trashed {
+                let _ = trash::untrash(trashed, target);
+            }
+            return Err(e);
+        }
+
+        let mut renamed = Renamed {
+            source: source.clone(),
+            target: target.clone(),
+            rule: rename.rule,
+            conflict: conflict.map(|strategy| Resolution {
+                strategy,
+                backup: backup.clone(),
+                trashed,
+            }),
+            relinked: Vec::new(),
+        };
+
+        // Relinking is part of the rename: if a link cannot follow, nothing moves
+        for (link, old) in links {
+            let relinked = Relinked {
+                new: old.with_file_name(target.file_name().unwrap_or_default()),
+                link,
+                old,
+            };
+            if let Err(e) = relinked.apply() {
+                return match self.revert(&renamed) {
+                    Ok(()) => Err(e),
+                    Err(undo) => Err(Error::Stale(format!(
+                        "{}\nRolling back failed, '{}' is still named '{}': {}",
+                        e,
+                        file_name(source),
+                        file_name(target),
+                        undo
+                    ))),
+                };
+            }
+            renamed.relinked.push(relinked);
+        }
+        Ok(renamed)
+    }
+
+    /// Perform several renames, all or nothing.
+    ///
+    /// `cancel` is checked before each rename; once it is set, or as soon as
+    /// a rename fails, the renames already done are reverted in reverse
+    /// order. Targets overwritten without trashing are kept under a
+    /// temporary name until the whole batch is through, so they can be put
+    /// back too. One that cannot be removed after all is left there and
+    /// reported as a backup, so it is not lost.
+    pub fn apply_all(&self, renames: &[Rename], cancel: &AtomicBool) -> Result<Vec<Renamed>> {
+        let mut done: Vec<Renamed> = Vec::with_capacity(renames.len());
+        for rename in renames {
+            let result = match cancel.load(Ordering::SeqCst) {
+                true => Err(Error::Aborted),
+                false
+                    if renames.len() > 1
+                        && rename.conflict == Some(OnConflict::Overwrite)
+                        && !self.trash
+                        && fs::symlink_metadata(&rename.target).is_ok() =>
+                {
+                    // Moving a directory aside would get around the kernel's
+                    // refusal to overwrite one that is not empty
+                    preflight::check_overwritable(&rename.target).and_then(|()| {
+                        self.apply(&Rename {
+                            backup: Some(displaced_path(&rename.target)),
+                            ..rename.clone()
+                        })
+                    })
+                }
+                false => self.apply(rename),
+            };
+            match result {
+                Ok(renamed) => done.push(renamed),
+                Err(e) => {
+                    for renamed in done.iter().rev() {
+                        if let Err(undo) = self.revert(renamed) {
+                            return Err(Error::Stale(format!(
+                                "{}\nRolling back failed, '{}' is still named '{}': {}",
+                                e,
+                                file_name(&renamed.source),
+                                file_name(&renamed.target),
+                                undo
+                            )));
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        // Only now are the overwritten targets really gone
+        for renamed in &mut done {
+            if let Some(conflict) = &mut renamed.conflict
+                && conflict.strategy == OnConflict::Overwrite
+                && let Some(displaced) = &conflict.backup
+            {
+                let removed = match fs::symlink_metadata(displaced) {
+                    Ok(m) if m.is_dir() => fs::remove_dir(displaced),
+                    _ => fs::remove_file(displaced),
+                };
+                if removed.is_ok() {
+                    conflict.backup = None;
+                }
+            }
+        }
+        Ok(done)
+    }
+
+    /// Undo a rename done by [`Renamer::apply`], putting back a displaced target.
+    fn revert(&self, renamed: &Renamed) -> Result<()> {
+        for relinked in renamed.relinked.iter().rev() {
+            relinked.revert()?;
+        }
+        transfer::move_path(&renamed.target, &renamed.source, self.verify)?;
+        match &renamed.conflict {
+            Some(Resolution {
+                backup: Some(backup),
+                ..
+            }) => fs::rename(backup, &renamed.target)
+                .map_err(|e| Error::io("Failed to restore backup", e)),
+            Some(Resolution {
+                trashed: Some(trashed),
+                ..
+            }) => trash::untrash(trashed, &renamed.target),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hidden name next to `target` where an overwritten target waits for the
+/// rest of its batch.
+fn displaced_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{}.rn-{}.displaced", name, std::process::id()))
+}
+
+fn file_name(path: &Path) -> std::borrow::Cow<'_, str> {
+    path.file_name().unwrap_or_default().to_string_lossy()
+}
+
+/// Collect the entries of `dir` into `paths`, descending into subdirectories
+/// while `max_depth` allows; 1 is `dir` alone. Symlinked directories are not
+/// followed, so the walk cannot loop, and unreadable subdirectories are
+/// passed over.
+///
+/// `visit` is asked about every entry, with whether it is a directory:
+/// `None` leaves it out, and everything below it, and `Some(descend)` keeps
+/// it and says whether a directory may be descended into.
+pub(crate) fn walk(
+    dir: &Path,
+    max_depth: Option<usize>,
+    visit: &mut dyn FnMut(&Path, bool) -> Option<bool>,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::io("Failed to read directory", e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io("Failed to read entry", e))?;
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let Some(descend) = visit(&path, is_dir) else {
+            continue;
+        };
+        if descend && is_dir && max_depth.is_none_or(|depth| depth > 1) {
+            let _ = walk(&path, max_depth.map(|depth| depth - 1), visit, paths);
+        }
+        paths.push(path);
+    }
+    Ok(())
+}
+
+/// How many characters `candidate` differs in length from `new_filename`.
+fn distance(candidate: &Candidate, new_filename: &str) -> usize {
+    file_name(&candidate.path)
+        .chars()
+        .count()
+        .abs_diff(new_filename.chars().count())
+}
+
+/// `path` with its directory canonicalized, keeping the file name itself as
+/// it is so that a symlink stays a symlink. `None` if the directory is gone.
+fn absolute(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    dir.canonicalize().ok().map(|dir| dir.join(name))
+}
+
+/// Split `new_name` into the canonical directory to search and the filename.
+///
+/// The search happens in the directory part of `new_name`, or the current
+/// directory when there is none (e.g. "./file" or "file").
+fn locate(new_name: &str) -> Result<(PathBuf, &str)> {
+    let new_name_path = Path::new(new_name);
+    let new_filename = new_name_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::InvalidName(new_name.to_string()))?;
+
+    let search_dir = match new_name_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => {
+            std::env::current_dir().map_err(|e| Error::io("Failed to get current directory", e))?
+        }
+    };
+
+    // Canonicalize the search directory to handle relative paths
+    let search_dir = search_dir
+        .canonicalize()
+        .map_err(|e| Error::io(format!("Invalid directory '{}'", search_dir.display()), e))?;
+    Ok((search_dir, new_filename))
+}
+
+impl Renamed {
+    /// Fsync every directory the rename touched, so it survives a crash.
+    pub fn sync(&self) -> Result<()> {
+        let mut dirs: Vec<&Path> = [
+            Some(self.source.as_path()),
+            Some(self.target.as_path()),
+            self.conflict.as_ref().and_then(|c| c.trashed.as_deref()),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(Path::parent)
+        .collect();
+        dirs.dedup();
+
+        dirs.into_iter().try_for_each(transfer::sync_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rename, Renamer, walk};
+    use crate::testing::scratch;
+    use crate::{Error, FileKind, Glob, MatchRule, OnConflict, SameData, SymlinkPolicy};
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::AtomicBool;
+
+    /// A fresh, empty directory under the system temp dir.
+    fn touch(dir: &Path, names: &[&str]) {
+        for name in names {
+            fs::write(dir.join(name), name).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_renamer_renames_unique_match() {
+        let dir = scratch("unique");
+        touch(&dir, &["report.csv", "notes.md"]);
+
+        let renamed = Renamer::new()
+            .rename(dir.join("report_v2.csv").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(renamed.source, dir.join("report.csv"));
+        assert_eq!(renamed.rule, MatchRule::Expansion);
+        assert!(dir.join("report_v2.csv").exists());
+        assert!(!dir.join("report.csv").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_ranks_ambiguous_candidates() {
+        let dir = scratch("ambiguous");
+        touch(&dir, &["data.txt", "data_old.json", "data.csv"]);
+
+        let err = Renamer::new()
+            .infer(dir.join("data.json").to_str().unwrap())
+            .unwrap_err();
+
+        let Error::Ambiguous { candidates, .. } = err else {
+            panic!("expected an ambiguous match, got {:?}", err);
+        };
+        let names: Vec<_> = candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["data.csv", "data.txt", "data_old.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_rules_and_filters_narrow_candidates() {
+        let dir = scratch("narrow");
+        touch(&dir, &["data.txt", "data_old.json", "data.csv"]);
+        let new_name = dir.join("data.json");
+
+        let rename = Renamer::new()
+            .rules([MatchRule::ExtensionChange])
+            .filter(|path| path.extension().is_some_and(|ext| ext != "txt"))
+            .infer(new_name.to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(
+            rename,
+            Some(Rename {
+                source: dir.join("data.csv"),
+                target: new_name.clone(),
+                requested: new_name,
+                rule: MatchRule::ExtensionChange,
+                conflict: None,
+                backup: None,
+            })
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_globs_match_relative_paths() {
+        let base = scratch("globs");
+        // The directories above the searched one share a name with the globs
+        let dir = base.join("target/proj");
+        fs::create_dir_all(dir.join("data")).unwrap();
+        touch(&dir, &["report.csv", "data/report.tsv"]);
+        let infer = |renamer: Renamer| {
+            renamer
+                .recursive(None)
+                .infer(dir.join("report.json").to_str().unwrap())
+        };
+        let glob = |pattern: &str| Glob::new(pattern).unwrap();
+
+        let rename = infer(
+            Renamer::new()
+                .exclude([glob("target/**")])
+                .include([glob("data/*")]),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(rename.source, dir.join("data/report.tsv"));
+
+        let rename = infer(Renamer::new().exclude([glob("data/**")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("report.csv"));
+
+        let err = infer(Renamer::new().include([glob("proj/*.csv")])).unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_candidates_from_list() {
+        let dir = scratch("listed");
+        fs::create_dir(dir.join("2024")).unwrap();
+        touch(&dir, &["report.csv", "report.txt", "2024/report.tsv"]);
+
+        // Unlisted files are ignored, and a listed file is renamed in place
+        let rename = Renamer::new()
+            .candidates_from([dir.join("2024/report.tsv"), dir.join("report.txt")])
+            .filter(|path| path.extension().is_some_and(|ext| ext != "txt"))
+            .infer(dir.join("report.csv").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("2024/report.tsv"));
+        assert_eq!(rename.target, dir.join("2024/report.csv"));
+
+        // Files outside the searched directory are not candidates
+        let err = Renamer::new()
+            .candidates_from([dir.join("report.txt")])
+            .infer(dir.join("2024/report.csv").to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_recursive_search() {
+        let dir = scratch("recursive");
+        fs::create_dir_all(dir.join("src/deep")).unwrap();
+        fs::create_dir(dir.join("docs")).unwrap();
+        touch(
+            &dir,
+            &["src/deep/main.rs", "docs/notes.txt", "src/notes.txt"],
+        );
+        let renamer = Renamer::new().recursive(None);
+
+        // Renamed within its own directory
+        let rename = renamer
+            .infer(dir.join("main_v2.rs").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("src/deep/main.rs"));
+        assert_eq!(rename.target, dir.join("src/deep/main_v2.rs"));
+
+        // Equally good matches in different directories are ambiguous
+        let err = renamer
+            .infer(dir.join("notes.md").to_str().unwrap())
+            .unwrap_err();
+        let Error::Ambiguous { candidates, .. } = err else {
+            panic!("expected an ambiguous match, got {:?}", err);
+        };
+        let names: Vec<_> = candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["docs/notes.txt", "src/notes.txt"]);
+
+        // Unless one ranks strictly higher
+        touch(&dir, &["src/notes_old.md"]);
+        fs::remove_file(dir.join("src/notes.txt")).unwrap();
+        let rename = renamer
+            .infer(dir.join("notes.md").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("docs/notes.txt"));
+
+        // The depth limit keeps the search shallow
+        let err = Renamer::new()
+            .recursive(Some(2))
+            .infer(dir.join("main_v2.rs").to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_dirs_are_optional_candidates() {
+        let dir = scratch("dirs");
+        fs::create_dir_all(dir.join("project")).unwrap();
+        fs::create_dir(dir.join("notes")).unwrap();
+        touch(&dir, &["project/main.rs", "notes.txt"]);
+        let new_name = dir.join("project_v2");
+        let new_name = new_name.to_str().unwrap();
+
+        let err = Renamer::new().infer(new_name).unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)));
+        let renamer = Renamer::new().dirs(true);
+        let rename = renamer.infer(new_name).unwrap().unwrap();
+        assert_eq!(rename.source, dir.join("project"));
+
+        // A directory and something inside it cannot be renamed together
+        let inner = dir.join("project/main_v2.rs");
+        let err = renamer
+            .infer_all([new_name, inner.to_str().unwrap()])
+            .unwrap_err();
+        assert!(matches!(err, (name, Error::NotPermitted(_)) if name == inner.to_str().unwrap()));
+
+        // Directories are marked as such when listed
+        let err = renamer
+            .infer(dir.join("notes.md").to_str().unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("  notes/\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_dangling_target_is_taken() {
+        let dir = scratch("dangling");
+        touch(&dir, &["notes.txt"]);
+        std::os::unix::fs::symlink("missing", dir.join("notes.md")).unwrap();
+        let new_name = dir.join("notes.md");
+
+        let err = Renamer::new()
+            .rename(new_name.to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, Error::TargetExists(_)));
+        assert!(fs::symlink_metadata(&new_name).unwrap().is_symlink());
+        let explanation = Renamer::new().explain(new_name.to_str().unwrap()).unwrap();
+        assert!(explanation.target_exists);
+
+        let rename = Renamer::new()
+            .on_conflict(OnConflict::Backup)
+            .infer(new_name.to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.conflict, Some(OnConflict::Backup));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_symlink_and_special_policy() {
+        let dir = scratch("symlinks");
+        fs::create_dir(dir.join("real")).unwrap();
+        touch(&dir, &["real/report.csv"]);
+        std::os::unix::fs::symlink("real/report.csv", dir.join("report.txt")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("old.log")).unwrap();
+        let fifo = std::ffi::CString::new(dir.join("pipe.in").to_str().unwrap()).unwrap();
+        // SAFETY: mkfifo only reads the NUL-terminated path
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let infer = |renamer: Renamer, name: &str| renamer.infer(dir.join(name).to_str().unwrap());
+
+        // The link itself by default, even when it dangles
+        let rename = infer(Renamer::new(), "report.md").unwrap().unwrap();
+        assert_eq!(rename.source, dir.join("report.txt"));
+        let rename = infer(Renamer::new(), "old.txt").unwrap().unwrap();
+        assert_eq!(rename.source, dir.join("old.log"));
+
+        // Or the file it leads to, renamed where it is
+        let rename = infer(Renamer::new().symlinks(SymlinkPolicy::Target), "report.md")
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("real/report.csv"));
+        assert_eq!(rename.target, dir.join("real/report.md"));
+
+        // Or not at all
+        let err = infer(Renamer::new().symlinks(SymlinkPolicy::Skip), "report.md").unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)));
+
+        // Special files only when asked for
+        assert!(infer(Renamer::new(), "pipe.out").is_err());
+        let candidates = Renamer::new()
+            .special(true)
+            .candidates(&dir, "pipe.out")
+            .unwrap();
+        assert_eq!(candidates[0].kind, FileKind::Fifo);
+        assert_eq!(candidates[0].to_string(), "pipe.in|");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_updates_symlinks() {
+        let dir = scratch("relink");
+        fs::create_dir(dir.join("sub")).unwrap();
+        touch(&dir, &["data.csv"]);
+        let symlink =
+            |to: &Path, link: &str| std::os::unix::fs::symlink(to, dir.join(link)).unwrap();
+        symlink(Path::new("data.csv"), "latest.csv");
+        symlink(&dir.join("data.csv"), "pinned.csv");
+        symlink(Path::new("../data.csv"), "sub/data.csv");
+        let new_name = dir.join("data_2023.csv");
+
+        // Links in the directory follow, keeping their form
+        let renamer = Renamer::new().update_symlinks(true);
+        let renamed = renamer.rename(new_name.to_str().unwrap()).unwrap().unwrap();
+        assert_eq!(renamed.relinked.len(), 2);
+        let read = |link: &str| fs::read_link(dir.join(link)).unwrap();
+        assert_eq!(read("latest.csv"), Path::new("data_2023.csv"));
+        assert_eq!(read("pinned.csv"), dir.join("data_2023.csv"));
+        assert_eq!(read("sub/data.csv"), Path::new("../data.csv"));
+
+        // And are put back along with the rename
+        renamer.revert(&renamed).unwrap();
+        assert_eq!(read("latest.csv"), Path::new("data.csv"));
+        assert_eq!(read("pinned.csv"), dir.join("data.csv"));
+
+        // A tree takes in links in subdirectories too
+        let renamed = Renamer::new()
+            .update_symlinks(true)
+            .symlink_tree(Some(dir.clone()))
+            .rename(new_name.to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(renamed.relinked.len(), 3);
+        assert_eq!(read("sub/data.csv"), Path::new("../data_2023.csv"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_groups_same_data() {
+        let dir = scratch("same");
+        touch(&dir, &["report.txt", "data.csv"]);
+        fs::hard_link(dir.join("report.txt"), dir.join("report.md")).unwrap();
+        fs::write(dir.join("data.tsv"), "data.csv").unwrap();
+        let new_name = dir.join("report.json");
+        let new_name = new_name.to_str().unwrap();
+
+        // Hardlinks are ambiguous by default, but said to be the same data
+        let err = Renamer::new().infer(new_name).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("report.md  (same data as report.txt)")
+        );
+        assert!(err.to_string().contains("They all hold the same data."));
+
+        // A policy settles on one name
+        let rename = Renamer::new()
+            .same_data(SameData::Best)
+            .infer(new_name)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("report.txt"));
+
+        // Identical copies only count when contents are compared
+        let groups = |compare| {
+            let candidates = Renamer::new()
+                .compare_contents(compare)
+                .candidates(&dir, "data.json")
+                .unwrap();
+            candidates.iter().map(|c| c.group).collect::<Vec<_>>()
+        };
+        assert_eq!(groups(false), [0, 1]);
+        assert_eq!(groups(true), [0, 0]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_skips_hidden_files() {
+        let dir = scratch("hidden");
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::create_dir(dir.join("docs")).unwrap();
+        touch(&dir, &[".git/notes.txt", "docs/notes.txt", ".env.local"]);
+        let new_name = dir.join("notes.md");
+        let new_name = new_name.to_str().unwrap();
+
+        let rename = Renamer::new()
+            .recursive(None)
+            .infer(new_name)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("docs/notes.txt"));
+        let err = Renamer::new()
+            .recursive(None)
+            .hidden(true)
+            .infer(new_name)
+            .unwrap_err();
+        assert!(matches!(err, Error::Ambiguous { .. }));
+
+        // A hidden new name looks for hidden files
+        let rename = Renamer::new()
+            .infer(dir.join(".env.prod").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join(".env.local"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_walk_leaves_pruned_directories() {
+        let dir = scratch("walk");
+        for sub in ["target/debug", ".git/objects", "src"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        touch(
+            &dir,
+            &["target/debug/app", ".git/objects/ab", "src/main.rs"],
+        );
+
+        let mut visited = Vec::new();
+        let mut paths = Vec::new();
+        let mut visit = |path: &Path, _: bool| {
+            visited.push(path.to_path_buf());
+            match path.file_name().unwrap().to_str().unwrap() {
+                "target" => None,
+                ".git" => Some(false),
+                _ => Some(true),
+            }
+        };
+        walk(&dir, None, &mut visit, &mut paths).unwrap();
+
+        // Nothing below a directory left out or not descended into is read
+        assert!(
+            !visited
+                .iter()
+                .any(|p| p.starts_with(dir.join("target/debug")))
+        );
+        assert!(
+            !visited
+                .iter()
+                .any(|p| p.starts_with(dir.join(".git/objects")))
+        );
+        paths.sort();
+        assert_eq!(
+            paths,
+            [dir.join(".git"), dir.join("src"), dir.join("src/main.rs")]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_respects_ignore_files() {
+        let dir = scratch("ignore");
+        for sub in [".git/info", "src", "target/debug", "logs/keep", "build"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        touch(
+            &dir,
+            &[
+                "src/main.o",
+                "target/debug/main.o",
+                "target/debug/app",
+                "logs/main.o",
+                "logs/keep/main.o",
+                "build/main.o",
+                "main.o",
+            ],
+        );
+        fs::write(dir.join(".gitignore"), "/target/\n*.o\n!src/*.o\n").unwrap();
+        fs::write(dir.join("logs/.ignore"), "!main.o\n").unwrap();
+        fs::write(dir.join(".git/info/exclude"), "build\n").unwrap();
+        let new_name = dir.join("main_v2.o");
+        let new_name = new_name.to_str().unwrap();
+
+        // Only the re-included files are left
+        let names = |renamer: Renamer| match renamer.infer(new_name).unwrap_err() {
+            Error::Ambiguous { candidates, .. } => {
+                candidates.into_iter().map(|c| c.name).collect::<Vec<_>>()
+            }
+            err => panic!("unexpected error: {err}"),
+        };
+        assert_eq!(
+            names(Renamer::new().recursive(None)),
+            ["logs/main.o", "src/main.o", "logs/keep/main.o"]
+        );
+        assert_eq!(
+            names(Renamer::new().recursive(None).ignore_files(false)).len(),
+            6
+        );
+
+        // Searching an ignored directory by name still finds its files
+        let rename = Renamer::new()
+            .infer(dir.join("target/debug/app_v2").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.source, dir.join("target/debug/app"));
+
+        // Outside a work tree only .ignore files count
+        fs::remove_dir_all(dir.join(".git")).unwrap();
+        assert_eq!(names(Renamer::new().recursive(None)).len(), 6);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_resolve_ignores_existing_target() {
+        let dir = scratch("resolve");
+        touch(&dir, &["plot.png", "plot.svg"]);
+
+        let resolved = Renamer::new()
+            .resolve(dir.join("plot.svg").to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(resolved.source, dir.join("plot.png"));
+        assert_eq!(resolved.rule, MatchRule::ExtensionChange);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_explain_keeps_all_candidates() {
+        let dir = scratch("explain");
+        touch(&dir, &["plot.png", "plot.svg", "plot.pdf"]);
+
+        let explanation = Renamer::new()
+            .explain(dir.join("plot.svg").to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(explanation.directory, dir);
+        assert!(explanation.target_exists);
+        assert_eq!(explanation.candidates.len(), 2);
+        assert!(matches!(explanation.outcome, Err(Error::TargetExists(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_infer_all_rejects_collisions() {
+        let dir = scratch("collisions");
+        touch(&dir, &["a.txt", "b.txt"]);
+        let name = |n: &str| dir.join(n).to_str().unwrap().to_string();
+
+        let renames = Renamer::new()
+            .infer_all([name("a.csv").as_str(), name("b_v2.txt").as_str()])
+            .unwrap();
+        assert_eq!(renames.len(), 2);
+
+        // Both names claim a.txt
+        let (first, second) = (name("a.csv"), name("a.md"));
+        let err = Renamer::new()
+            .infer_all([first.as_str(), second.as_str()])
+            .unwrap_err();
+        assert_eq!(err.0, second);
+        assert!(matches!(err.1, Error::NotPermitted(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_apply_all_rolls_back() {
+        let dir = scratch("rollback");
+        touch(&dir, &["a.txt", "b.txt"]);
+        let renamer = Renamer::new();
+        let mut renames: Vec<Rename> = renamer
+            .infer_all([
+                dir.join("a.csv").to_str().unwrap(),
+                dir.join("b.csv").to_str().unwrap(),
+            ])
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // The second rename fails once its source is gone
+        renames[1].source = dir.join("missing.txt");
+        assert!(
+            renamer
+                .apply_all(&renames, &AtomicBool::new(false))
+                .is_err()
+        );
+        assert!(dir.join("a.txt").exists());
+        assert!(!dir.join("a.csv").exists());
+
+        // A cancelled batch renames nothing
+        let err = renamer
+            .apply_all(&renames, &AtomicBool::new(true))
+            .unwrap_err();
+        assert!(matches!(err, Error::Aborted));
+        assert!(dir.join("a.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_apply_all_restores_overwritten_targets() {
+        let dir = scratch("overwrite-batch");
+        touch(&dir, &["a.txt", "a.csv", "b.txt"]);
+        let renamer = Renamer::new().on_conflict(OnConflict::Overwrite);
+        let infer = || -> Vec<Rename> {
+            renamer
+                .infer_all([
+                    dir.join("a.csv").to_str().unwrap(),
+                    dir.join("b.csv").to_str().unwrap(),
+                ])
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        // A failure later in the batch brings the overwritten target back
+        let mut renames = infer();
+        renames[1].source = dir.join("missing.txt");
+        assert!(
+            renamer
+                .apply_all(&renames, &AtomicBool::new(false))
+                .is_err()
+        );
+        assert_eq!(fs::read_to_string(dir.join("a.csv")).unwrap(), "a.csv");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a.txt");
+
+        // Once the batch is through, nothing is left behind
+        let renamed = renamer
+            .apply_all(&infer(), &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.csv")).unwrap(), "a.txt");
+        assert!(renamed[0].conflict.as_ref().unwrap().backup.is_none());
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a.csv", "b.csv"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_overwrite_refuses_non_empty_directory() {
+        let dir = scratch("overwrite-dir");
+        fs::create_dir_all(dir.join("proj")).unwrap();
+        fs::create_dir_all(dir.join("proj_v2")).unwrap();
+        touch(&dir, &["proj_v2/keep", "a.txt"]);
+        let renamer = Renamer::new().dirs(true).on_conflict(OnConflict::Overwrite);
+
+        let err = renamer
+            .infer(dir.join("proj_v2").to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, Error::NotPermitted(_)));
+
+        // Nor is it moved aside in a batch, such as a plan made before the
+        // directory filled up
+        let renames = [
+            renamer
+                .infer(dir.join("a.csv").to_str().unwrap())
+                .unwrap()
+                .unwrap(),
+            Rename {
+                source: dir.join("proj"),
+                target: dir.join("proj_v2"),
+                requested: dir.join("proj_v2"),
+                rule: MatchRule::Expansion,
+                conflict: Some(OnConflict::Overwrite),
+                backup: None,
+            },
+        ];
+        let err = renamer
+            .apply_all(&renames, &AtomicBool::new(false))
+            .unwrap_err();
+        assert!(matches!(err, Error::NotPermitted(_)));
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a.txt", "proj", "proj_v2"]);
+        assert!(dir.join("proj_v2/keep").exists());
+
+        // An empty one is replaced, as the kernel would
+        fs::remove_file(dir.join("proj_v2/keep")).unwrap();
+        let rename = renamer
+            .infer(dir.join("proj_v2").to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        renamer.apply(&rename).unwrap();
+        assert!(!dir.join("proj").exists() && dir.join("proj_v2").is_dir());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_renamer_conflict_strategies() {
+        let dir = scratch("conflict");
+        touch(&dir, &["log.txt", "log.csv"]);
+        let new_name = dir.join("log.csv");
+        let new_name = new_name.to_str().unwrap();
+
+        assert!(matches!(
+            Renamer::new().infer(new_name),
+            Err(Error::TargetExists(_))
+        ));
+        assert_eq!(
+            Renamer::new()
+                .on_conflict(OnConflict::Skip)
+                .infer(new_name)
+                .unwrap(),
+            None
+        );
+
+        let renamed = Renamer::new()
+            .on_conflict(OnConflict::Backup)
+            .rename(new_name)
+            .unwrap()
+            .unwrap();
+        assert_eq!(renamed.conflict.unwrap().backup, Some(dir.join("log.csv~")));
+        assert_eq!(fs::read_to_string(dir.join("log.csv")).unwrap(), "log.txt");
+        assert_eq!(fs::read_to_string(dir.join("log.csv~")).unwrap(), "log.csv");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
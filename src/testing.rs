@@ -0,0 +1,25 @@
+//! Fixtures shared by the tests of the library and of `rn`, which includes
+//! this file as its own module.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh, empty directory for a test, removed again by the test itself.
+///
+/// The path is canonicalized, so it compares equal to the paths the renamer
+/// resolves even where the temp dir is behind a symlink, like `/var` on
+/// macOS. Every call gets a directory of its own, so tests running side by
+/// side never share one.
+pub fn scratch(name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "snipren-{}-{}-{}",
+        name,
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
@@ -0,0 +1,316 @@
+use crate::{Error, Result};
+use std::fs::{self, File, FileTimes};
+use std::hash::Hasher;
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Files at least this large get progress output while copying.
+const PROGRESS_THRESHOLD: u64 = 64 * 1024 * 1024;
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Rename `from` to `to`, falling back to copy, verify and unlink when the
+/// two are on different filesystems (`EXDEV`).
+///
+/// With `verify` the copy is also compared by content hash, not just size.
+pub fn move_path(from: &Path, to: &Path, verify: bool) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => copy_across(from, to, verify),
+        Err(e) => Err(Error::io("Failed to rename", e)),
+    }
+}
+
+/// Copy `from` next to `to`, atomically rename it into place, then unlink `from`.
+fn copy_across(from: &Path, to: &Path, verify: bool) -> Result<()> {
+    let metadata = fs::symlink_metadata(from)
+        .map_err(|e| Error::io(format!("Failed to stat '{}'", from.display()), e))?;
+
+    let tmp = temp_path(to);
+    let result = if metadata.file_type().is_symlink() {
+        fs::read_link(from)
+            .and_then(|link| std::os::unix::fs::symlink(link, &tmp))
+            .map_err(|e| Error::io(format!("Failed to copy symlink '{}'", from.display()), e))
+    } else if metadata.is_file() {
+        copy_file(from, &tmp, &metadata)
+    } else {
+        Err(Error::io(
+            format!("Cannot move '{}' across filesystems", from.display()),
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only files and symlinks can be copied",
+            ),
+        ))
+    };
+
+    // Nothing may be left behind when any step before the final rename fails
+    let result = result
+        .and_then(|_| verify_copy(from, &tmp, &metadata, verify))
+        .and_then(|_| {
+            fs::rename(&tmp, to).map_err(|e| Error::io("Failed to move copy into place", e))
+        })
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        });
+    result?;
+
+    // The new name must be on disk before the only other copy goes away
+    sync_dir(to.parent().unwrap_or(Path::new(".")))?;
+    fs::remove_file(from).map_err(|e| {
+        Error::io(
+            format!(
+                "Copied to '{}' but failed to remove '{}'",
+                to.display(),
+                from.display()
+            ),
+            e,
+        )
+    })
+}
+
+/// Check the copy against the source by size and, with `verify`, content hash.
+fn verify_copy(from: &Path, copy: &Path, metadata: &fs::Metadata, verify: bool) -> Result<()> {
+    if !metadata.is_file() {
+        return Ok(());
+    }
+
+    let copied = fs::metadata(copy)
+        .map_err(|e| Error::io(format!("Failed to stat '{}'", copy.display()), e))?;
+    let mismatch = if copied.len() != metadata.len() {
+        "size"
+    } else if verify && content_hash(from)? != content_hash(copy)? {
+        "checksum"
+    } else {
+        return Ok(());
+    };
+
+    Err(Error::io(
+        format!(
+            "Copy of '{}' failed verification; source kept",
+            from.display()
+        ),
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} mismatch", mismatch)),
+    ))
+}
+
+/// Copy data, permissions, ownership, timestamps and xattrs, then fsync.
+fn copy_file(from: &Path, to: &Path, metadata: &fs::Metadata) -> Result<()> {
+    let fail = |e: io::Error| Error::io(format!("Failed to copy '{}'", from.display()), e);
+
+    let mut src = File::open(from).map_err(fail)?;
+    let mut dst = File::create_new(to).map_err(fail)?;
+
+    let total = metadata.len();
+    let show_progress = total >= PROGRESS_THRESHOLD && io::stderr().is_terminal();
+    let name = from
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut copied = 0u64;
+    let mut last_percent = None;
+    loop {
+        let n = src.read(&mut buf).map_err(fail)?;
+        if n == 0 {
+            break;
+        }
+        dst.write_all(&buf[..n]).map_err(fail)?;
+        copied += n as u64;
+
+        if show_progress && let Some(line) = progress(&name, copied, total, &mut last_percent) {
+            eprint!("{}", line);
+        }
+    }
+    if show_progress {
+        eprintln!();
+    }
+
+    dst.set_permissions(fs::Permissions::from_mode(metadata.mode()))
+        .map_err(fail)?;
+    // Ownership can only be kept with enough privilege; a plain user keeps their own
+    // SAFETY: fchown on a valid descriptor owned by `dst`
+    unsafe {
+        use std::os::fd::AsRawFd;
+        libc::fchown(dst.as_raw_fd(), metadata.uid(), metadata.gid());
+    }
+    copy_xattrs(from, to)?;
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed().map_err(fail)?)
+        .set_modified(metadata.modified().map_err(fail)?);
+    dst.set_times(times).map_err(fail)?;
+
+    dst.sync_all().map_err(fail)
+}
+
+/// The progress line for `copied` of `total` bytes, when the percentage
+/// moved on since `last_percent`.
+fn progress(name: &str, copied: u64, total: u64, last_percent: &mut Option<u64>) -> Option<String> {
+    let percent = copied * 100 / total;
+    if *last_percent == Some(percent) {
+        return None;
+    }
+    *last_percent = Some(percent);
+    Some(format!("\rcopying {}: {:>3}%", name, percent))
+}
+
+/// Copy extended attributes, tolerating a target filesystem without support.
+fn copy_xattrs(from: &Path, to: &Path) -> Result<()> {
+    let Ok(names) = xattr::list(from) else {
+        return Ok(());
+    };
+
+    for name in names {
+        let Ok(Some(value)) = xattr::get(from, &name) else {
+            continue;
+        };
+        match xattr::set(to, &name, &value) {
+            Ok(()) => {}
+            Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+            Err(e) => {
+                return Err(Error::io(
+                    format!(
+                        "Failed to copy extended attribute '{}'",
+                        name.to_string_lossy()
+                    ),
+                    e,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hash the contents of a file, for comparing copies.
+pub fn content_hash(path: &Path) -> Result<u64> {
+    let fail = |e: io::Error| Error::io(format!("Failed to read '{}'", path.display()), e);
+
+    let mut file = File::open(path).map_err(fail)?;
+    let mut hasher = std::hash::DefaultHasher::new();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        let n = file.read(&mut buf).map_err(fail)?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+/// Hidden temporary name next to `to`, unique to this process.
+pub fn temp_path(to: &Path) -> PathBuf {
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    to.with_file_name(format!(".{}.rn-{}.tmp", name, std::process::id()))
+}
+
+/// Flush directory entry changes to disk.
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|e| Error::io(format!("Failed to sync '{}'", dir.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_across, progress, temp_path, verify_copy};
+    use crate::testing::scratch;
+    use std::fs::{self, FileTimes};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_transfer_copy_across_keeps_metadata() {
+        let dir = scratch("copy");
+        let (from, to) = (dir.join("data.txt"), dir.join("data.csv"));
+        fs::write(&from, "a,b\n1,2\n").unwrap();
+        fs::set_permissions(&from, fs::Permissions::from_mode(0o640)).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let file = fs::File::options().write(true).open(&from).unwrap();
+        file.set_times(FileTimes::new().set_modified(mtime))
+            .unwrap();
+        drop(file);
+        let xattrs = xattr::set(&from, "user.snipren.test", b"kept").is_ok();
+
+        copy_across(&from, &to, true).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "a,b\n1,2\n");
+        let metadata = fs::metadata(&to).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        if xattrs {
+            assert_eq!(
+                xattr::get(&to, "user.snipren.test").unwrap(),
+                Some(b"kept".to_vec())
+            );
+        }
+        assert!(!temp_path(&to).exists());
+
+        // Symlinks are recreated, not followed
+        std::os::unix::fs::symlink("data.csv", dir.join("link")).unwrap();
+        copy_across(&dir.join("link"), &dir.join("link2"), false).unwrap();
+        assert_eq!(
+            fs::read_link(dir.join("link2")).unwrap(),
+            PathBuf::from("data.csv")
+        );
+        assert!(fs::symlink_metadata(dir.join("link")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_copy_across_failure_keeps_source() {
+        let dir = scratch("fail");
+        let from = dir.join("data.txt");
+        fs::write(&from, "data").unwrap();
+
+        // A directory in the way makes the final rename fail
+        let to = dir.join("data.csv");
+        fs::create_dir(&to).unwrap();
+        fs::write(to.join("inside"), "").unwrap();
+        assert!(copy_across(&from, &to, true).is_err());
+        assert_eq!(fs::read_to_string(&from).unwrap(), "data");
+        assert!(!temp_path(&to).exists());
+
+        // Directories cannot be copied across
+        assert!(copy_across(&to, &dir.join("moved"), false).is_err());
+        assert!(to.join("inside").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_verify_copy() {
+        let dir = scratch("verify");
+        let from = dir.join("data.txt");
+        fs::write(&from, "abcd").unwrap();
+        let metadata = fs::metadata(&from).unwrap();
+
+        let copy = dir.join("copy");
+        fs::write(&copy, "abc").unwrap();
+        let err = verify_copy(&from, &copy, &metadata, false).unwrap_err();
+        assert!(err.to_string().contains("failed verification"));
+
+        // Same size, different bytes: only the checksum tells
+        fs::write(&copy, "abcx").unwrap();
+        assert!(verify_copy(&from, &copy, &metadata, false).is_ok());
+        assert!(verify_copy(&from, &copy, &metadata, true).is_err());
+        fs::write(&copy, "abcd").unwrap();
+        assert!(verify_copy(&from, &copy, &metadata, true).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_progress() {
+        let mut last = None;
+        assert_eq!(
+            progress("big.iso", 0, 200, &mut last).as_deref(),
+            Some("\rcopying big.iso:   0%")
+        );
+        assert_eq!(progress("big.iso", 1, 200, &mut last), None);
+        assert_eq!(
+            progress("big.iso", 200, 200, &mut last).as_deref(),
+            Some("\rcopying big.iso: 100%")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{Error, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// Move `path` into the freedesktop.org trash and return where it ended up.
+///
+/// Uses the home trash (`$XDG_DATA_HOME/Trash`) when it is on the same
+/// filesystem, otherwise `$topdir/.Trash-$uid` on the file's own mount, as the
+/// Trash specification prescribes. Desktop tools can restore from either.
+pub fn trash(path: &Path) -> Result<PathBuf> {
+    let path = std::path::absolute(path)
+        .map_err(|e| Error::io(format!("Invalid path '{}'", path.display()), e))?;
+    let metadata = fs::symlink_metadata(&path)
+        .map_err(|e| Error::io(format!("Failed to stat '{}'", path.display()), e))?;
+
+    let home_trash = data_home().map(|dir| dir.join("Trash"));
+    let same_device = |dir: &Path| {
+        dir.ancestors()
+            .find_map(|d| fs::metadata(d).ok())
+            .is_some_and(|m| m.dev() == metadata.dev())
+    };
+
+    match home_trash {
+        Some(trash_dir) if same_device(&trash_dir) => move_to(&path, &trash_dir, &path),
+        _ => {
+            let topdir = mount_point(&path, metadata.dev());
+            // SAFETY: getuid cannot fail
+            let uid = unsafe { libc::getuid() };
+            let trash_dir = topdir.join(format!(".Trash-{}", uid));
+            // Paths in a $topdir trash are relative to $topdir
+            let recorded = path.strip_prefix(&topdir).unwrap_or(&path).to_path_buf();
+            move_to(&path, &trash_dir, &recorded)
+        }
+    }
+}
+
+/// Reserve a `.trashinfo` entry in `trash_dir` and move `path` into it.
+fn move_to(path: &Path, trash_dir: &Path, recorded: &Path) -> Result<PathBuf> {
+    let files = trash_dir.join("files");
+    let info = trash_dir.join("info");
+    for dir in [trash_dir, &files, &info] {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .map_err(|e| Error::io(format!("Failed to create trash '{}'", dir.display()), e))?;
+    }
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidName(path.display().to_string()))?
+        .to_string_lossy()
+        .into_owned();
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(recorded),
+        local_timestamp()
+    );
+
+    // Creating the info file exclusively is what claims a name in the trash
+    for n in 1..=u16::MAX {
+        let trashed_name = match n {
+            1 => name.clone(),
+            n => format!("{}.{}", name, n),
+        };
+        let info_path = info.join(format!("{}.trashinfo", trashed_name));
+        let trashed_path = files.join(&trashed_name);
+        if fs::symlink_metadata(&trashed_path).is_ok() {
+            continue;
+        }
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(Error::io("Failed to write trash info", e)),
+        };
+
+        if let Err(e) = file
+            .write_all(contents.as_bytes())
+            .and_then(|_| fs::rename(path, &trashed_path))
+        {
+            let _ = fs::remove_file(&info_path);
+            return Err(Error::io(
+                format!("Failed to move '{}' to the trash", path.display()),
+                e,
+            ));
+        }
+        return Ok(trashed_path);
+    }
+
+    Err(Error::io(
+        format!("Failed to move '{}' to the trash", path.display()),
+        io::Error::new(io::ErrorKind::AlreadyExists, "no free name in the trash"),
+    ))
+}
+
+/// Put a trashed file back where it came from and drop its `.trashinfo`.
+pub fn untrash(trashed: &Path, original: &Path) -> Result<()> {
+    fs::rename(trashed, original).map_err(|e| {
+        Error::io(
+            format!("Failed to restore '{}' from the trash", original.display()),
+            e,
+        )
+    })?;
+
+    if let (Some(files), Some(name)) = (trashed.parent(), trashed.file_name()) {
+        let mut info_name = name.to_os_string();
+        info_name.push(".trashinfo");
+        let _ = fs::remove_file(files.with_file_name("info").join(info_name));
+    }
+    Ok(())
+}
+
+/// `$XDG_DATA_HOME`, or `$HOME/.local/share` when unset or relative.
+fn data_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")),
+    }
+}
+
+/// Walk up from `path` to the highest ancestor still on device `dev`.
+fn mount_point(path: &Path, dev: u64) -> PathBuf {
+    let mut topdir = path.parent().unwrap_or(Path::new("/"));
+    while let Some(parent) = topdir.parent() {
+        match fs::metadata(parent) {
+            Ok(m) if m.dev() == dev => topdir = parent,
+            _ => break,
+        }
+    }
+    topdir.to_path_buf()
+}
+
+/// Percent-encode a path as the Trash spec requires (RFC 2396, keeping `/`).
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Current local time as `YYYY-MM-DDThh:mm:ss`, the `DeletionDate` format.
+fn local_timestamp() -> String {
+    // SAFETY: time and localtime_r only write to the provided buffers
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mount_point, move_to, percent_encode, untrash};
+    use crate::testing::scratch;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_trash_percent_encode() {
+        assert_eq!(
+            percent_encode(Path::new("/home/me/a-b_c.txt~")),
+            "/home/me/a-b_c.txt~"
+        );
+        assert_eq!(
+            percent_encode(Path::new("/tmp/my file%.txt")),
+            "/tmp/my%20file%25.txt"
+        );
+        assert_eq!(percent_encode(Path::new("données")), "donn%C3%A9es");
+    }
+
+    #[test]
+    fn test_trash_move_to_and_untrash() {
+        let dir = scratch("move");
+        let trash_dir = dir.join("Trash");
+        let file = dir.join("notes.txt");
+
+        // Equal names get numbered instead of replacing each other
+        fs::write(&file, "first").unwrap();
+        let first = move_to(&file, &trash_dir, &file).unwrap();
+        fs::write(&file, "second").unwrap();
+        let second = move_to(&file, &trash_dir, &file).unwrap();
+        assert_eq!(first, trash_dir.join("files/notes.txt"));
+        assert_eq!(second, trash_dir.join("files/notes.txt.2"));
+        assert!(!file.exists());
+
+        let info = fs::read_to_string(trash_dir.join("info/notes.txt.2.trashinfo")).unwrap();
+        assert!(info.starts_with(&format!(
+            "[Trash Info]\nPath={}\nDeletionDate=",
+            file.display()
+        )));
+
+        untrash(&second, &file).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+        assert!(!trash_dir.join("info/notes.txt.2.trashinfo").exists());
+        assert!(trash_dir.join("info/notes.txt.trashinfo").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trash_topdir_records_relative_path() {
+        let dir = scratch("topdir");
+        let file = dir.join("notes.txt");
+        fs::write(&file, "").unwrap();
+
+        // The mount holding the file, as the fallback trash lives there
+        let dev = fs::metadata(&file).unwrap().dev();
+        let topdir = mount_point(&file, dev);
+        assert!(file.starts_with(&topdir));
+        assert!(
+            topdir
+                .parent()
+                .is_none_or(|p| fs::metadata(p).unwrap().dev() != dev)
+        );
+
+        let recorded = file.strip_prefix(&dir).unwrap();
+        move_to(&file, &dir.join(".Trash-1000"), recorded).unwrap();
+        let info = fs::read_to_string(dir.join(".Trash-1000/info/notes.txt.trashinfo")).unwrap();
+        assert!(info.contains("\nPath=notes.txt\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}